@@ -1,143 +1,3152 @@
-use egui::{
-    color_picker, epaint::Hsva, mutex::Mutex, vec2, ColorImage, Id, ImageSource, PaintCallback,
-    Pos2, Rect, Sense, Slider, Vec2,
+use egui::{color_picker, mutex::Mutex, vec2, Pos2, Sense, Slider, Vec2};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, sync::Arc};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    export::{read_metadata, save_image_numbered},
+    watch::FileWatcher,
+};
+use crate::{
+    export::{save_image, Downsampling, ExportDepth, Pixels},
+    fixed::Fixed,
+    orbit::{orbit, Orbit},
+    palette::{builtin_palettes, parse_gimp, Palette},
+    renderer::*,
+    uniforms::*,
 };
-use std::{fs::File, io::Write, sync::Arc};
 
-use crate::renderer::*;
+const DARK_MODE_KEY: &str = "dark_mode";
+const PALETTES_KEY: &str = "palettes";
+const FRAME_RATE_KEY: &str = "max_frame_rate";
+const SCROLL_ZOOM_KEY: &str = "scroll_zoom";
+
+/// Zoom change for a single press of the `+`/`-` keys.
+const KEY_ZOOM_FACTOR: f32 = 1.25;
+
+/// Zoom change for a click on the on-screen `+`/`−` buttons.
+const BUTTON_ZOOM_FACTOR: f32 = 2.;
+
+/// Points of plain wheel scrolling that zoom by a factor of e, like Ctrl+scroll in egui.
+const SCROLL_ZOOM_POINTS: f32 = 200.;
+
+/// Seconds after the last keystroke before the custom function is validated.
+const VALIDATION_DELAY: f64 = 0.5;
+
+/// Bars of the escape iteration histogram shown with the statistics.
+const HISTOGRAM_BINS: usize = 64;
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct UniformData {
-    pub center: Vec2,
-    pub zoom: f32,
-    pub resolution: Vec2,
-    pub window_offset: Vec2,
-    pub cycles: i32,
-    pub start_color: Hsva,
-    pub end_color: Hsva,
+/// Largest side of the image the statistics are computed from, a sample of the view that's
+/// quick to read back.
+const STATS_SIZE: u32 = 512;
+
+/// Largest side of the thumbnail of the last screenshot.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Seconds between updates of the statistics while they are live.
+const LIVE_STATS_INTERVAL: f64 = 0.5;
+
+/// Everything needed to reproduce a view, embedded into exported images.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settings {
+    pub fractal_type: FractalType,
+    pub custom_fractal_function: String,
+    pub uniform_data: UniformData,
+}
+
+/// Key of the PNG text chunk holding the [`Settings`] as JSON.
+pub const METADATA_KEY: &str = "fractal-gui";
+
+/// Environment variable with the path of a [`Settings`] file to start with.
+#[cfg(not(target_arch = "wasm32"))]
+pub const CONFIG_ENV: &str = "FRACTAL_GUI_CONFIG";
+
+/// Environment variable turning vsync off when set to `0`. The window is created before the
+/// app's storage is read, so this can't be a setting.
+pub const VSYNC_ENV: &str = "FRACTAL_GUI_VSYNC";
+
+/// Reads the [`Settings`] to start with from the file named by [`CONFIG_ENV`], or from
+/// `fractal-gui/default.json` in the user's config directory. Returns `None` when neither
+/// is given, so the defaults apply.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_startup_settings() -> Option<Result<Settings, String>> {
+    let path = match std::env::var_os(CONFIG_ENV) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+                })?;
+            let path = config_dir.join("fractal-gui").join("default.json");
+            if !path.exists() {
+                return None;
+            }
+            path
+        }
+    };
+    let settings = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Invalid settings in {}: {e}", path.display()));
+    Some(settings)
 }
 
 pub struct App {
     /// Behind an `Arc<Mutex<…>>` so we can pass it to [`egui::PaintCallback`] and paint later.
     renderer: Arc<Mutex<Renderer>>,
     uniform_data: UniformData,
+    fractal_type: FractalType,
+    custom_fractal_function: String,
+    /// Compiler log of the last fractal function that failed to compile.
+    shader_error: Option<ShaderError>,
+    /// Result of validating the editor contents, `None` while waiting for the user to stop typing.
+    validation: Option<Result<(), ShaderError>>,
+    /// How long compiling and linking the last custom function took.
+    compile_time: Option<std::time::Duration>,
+    /// Time of the last edit of the custom function that wasn't validated yet.
+    last_edit: Option<f64>,
+    /// Custom function from before the last preset was inserted, for undoing it.
+    function_undo: Option<String>,
+    /// External file the custom function is reloaded from whenever it changes.
+    #[cfg(not(target_arch = "wasm32"))]
+    shader_watcher: Option<FileWatcher>,
+    /// Outcome of the last screenshot, shown under the button.
+    screenshot_status: Option<Result<String, String>>,
+    /// The screenshot being rendered.
+    export_job: Option<ExportJob>,
+    screenshot: Option<Screenshot>,
+    /// Folder picked in the last save dialog.
+    save_directory: Option<PathBuf>,
+    /// Save screenshots into `save_directory` with numbered names instead of asking.
+    #[cfg(not(target_arch = "wasm32"))]
+    numbered_screenshots: bool,
+    export_depth: ExportDepth,
+    dark_mode: bool,
+    settings_shown: bool,
+    compare: Option<CompareView>,
+    demo: Option<Demo>,
+    color_cycling: bool,
+    /// Gradient lengths per second.
+    color_cycle_speed: f32,
+    show_readout: bool,
+    /// Draw the real and imaginary axes over the views.
+    show_axes: bool,
+    /// Render the single view into `view_texture` and show it as an image, which egui
+    /// composites like any other instead of leaving a hole for the paint callback. Slower,
+    /// every frame is read back from the GPU.
+    render_to_texture: bool,
+    /// The single view while `render_to_texture` is set.
+    view_texture: Option<egui::TextureHandle>,
+    /// Statistics of the main view, computed on request and shown over it.
+    stats: Option<ViewStats>,
+    /// Compute `stats` again every [`LIVE_STATS_INTERVAL`], so they follow the view.
+    live_stats: bool,
+    /// Time of the last update of the live statistics.
+    stats_time: f64,
+    /// Draws the Mandelbrot set next to the constants of custom functions.
+    julia_inset: Arc<Mutex<Renderer>>,
+    /// Animate switching between fractal types.
+    morph_types: bool,
+    /// Progress of the animated switch in `0..1`, while it runs.
+    morph: Option<f32>,
+    /// Stop iterating periodic orbits early, for the Mandelbrot set.
+    period_checking: bool,
+    /// How to keep the Mandelbrot set precise once the zoom passes [`DEEP_ZOOM_START`].
+    deep_zoom: DeepZoom,
+    /// Lower the iterations of the live view while frames are slow.
+    performance_mode: bool,
+    /// Space the magnification slider logarithmically, every notch magnifying by the same factor.
+    log_magnification: bool,
+    /// Frames per second animations repaint at most with, persisted in eframe's storage.
+    /// `None` repaints as fast as vsync allows.
+    max_frame_rate: Option<f32>,
+    /// Iterations painted in the live view when the performance mode lowered them,
+    /// exports always use all of them.
+    live_cycles: Option<i32>,
+    /// Saved gradients, persisted in eframe's storage.
+    palettes: Vec<Palette>,
+    /// Name the current gradient is saved under.
+    palette_name: String,
+    /// Whether applying a palette keeps the start and the end color.
+    locked_colors: [bool; 2],
+    /// Why the last palette import failed.
+    palette_error: Option<String>,
+    /// Palette picked in the browser, filled in asynchronously.
+    #[cfg(target_arch = "wasm32")]
+    palette_import: Arc<Mutex<Option<Result<Palette, String>>>>,
+    /// Zoom only in powers of `zoom_step`, so levels can be repeated exactly.
+    snap_zoom: bool,
+    /// Zoom with plain wheel scrolling, otherwise only pinching and Ctrl+scroll zoom.
+    /// Persisted in eframe's storage.
+    scroll_zoom: bool,
+    zoom_step: f32,
+    /// Export at the size of the view in points instead of physical pixels,
+    /// which differ on high-DPI displays.
+    export_logical_pixels: bool,
+    /// Supersampling factor of screenshots, the live view always renders one sample per pixel.
+    export_samples: u32,
+    /// How supersampled screenshots are scaled down.
+    export_downsampling: Downsampling,
+    /// Leave the set transparent in color exports, see [`UniformData::transparent_interior`].
+    export_transparent_interior: bool,
+    /// Part of the main view to export instead of all of it, see [`UniformData::cropped`].
+    export_region: Option<egui::Rect>,
+    /// The go-to dialog, while it's open.
+    go_to: Option<GoTo>,
+    flight: Option<Flight>,
 }
 
-impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let gl = cc
-            .gl
-            .as_ref()
-            .expect("You need to run eframe with the glow backend");
+/// Frame time above which the performance mode lowers the iterations of the live view.
+const SLOW_FRAME_TIME: f32 = 1. / 30.;
+
+/// Frame time below which the performance mode raises them again.
+const FAST_FRAME_TIME: f32 = 1. / 50.;
+
+/// The performance mode doesn't go below this many iterations.
+const MIN_LIVE_CYCLES: i32 = 50;
+
+/// Places worth zooming into in the demo mode, in fractal coordinates.
+const DEMO_POINTS: [Vec2; 5] = [
+    vec2(-0.743_643_9, 0.131_825_9),
+    vec2(0.285, 0.01),
+    vec2(-0.088, 0.654),
+    vec2(-1.768_778_8, 0.001_738_6),
+    vec2(-0.761_574, -0.084_759_2),
+];
+
+/// Magnification per second in the demo mode.
+const DEMO_ZOOM_SPEED: f32 = 1.5;
+
+/// Zoom at which the demo starts over, single precision gets blocky soon after.
+const DEMO_MAX_ZOOM: f32 = 2e4;
+
+/// The last exported image, kept to preview it and to save it again.
+struct Screenshot {
+    width: u32,
+    height: u32,
+    pixels: Pixels,
+    metadata: String,
+    preview: egui::TextureHandle,
+}
+
+/// A screenshot being rendered a band of rows per frame, so the app can show its progress
+/// and the export can be cancelled in between. Every band is a complete render of its own,
+/// so no GL objects are kept between frames.
+struct ExportJob {
+    /// The exported view at the size of the image, saved as its metadata.
+    uniform_data: UniformData,
+    /// The exported view at the rendered size, `samples` times larger.
+    render_data: UniformData,
+    depth: ExportDepth,
+    samples: u32,
+    /// The export quality asked for, more than `samples` when the texture limit lowered it.
+    wanted_samples: u32,
+    /// Rendered rows, from the top.
+    pixels: Option<Pixels>,
+    rows_done: u32,
+    /// Time the export started, in egui's clock.
+    started: f64,
+}
+
+impl ExportJob {
+    fn render_size(&self) -> (u32, u32) {
+        (
+            self.render_data.resolution.x as u32,
+            self.render_data.resolution.y as u32,
+        )
+    }
+
+    /// Rendered fraction of the image.
+    fn progress(&self) -> f32 {
+        self.rows_done as f32 / self.render_size().1 as f32
+    }
+}
+
+/// Renders `uniform_data` with `renderer` in the format of `depth`.
+fn render_export(
+    gl: &glow::Context,
+    renderer: &Renderer,
+    depth: ExportDepth,
+    width: u32,
+    height: u32,
+    uniform_data: UniformData,
+) -> Result<Pixels, String> {
+    let progress = &mut |_| {};
+    match depth {
+        ExportDepth::Eight => renderer
+            .render_to_buffer(gl, width, height, uniform_data, progress)
+            .map(Pixels::Rgba8),
+        ExportDepth::Sixteen => renderer
+            .render_to_buffer_16(gl, width, height, uniform_data, progress)
+            .map(Pixels::Rgba16),
+        ExportDepth::Float => renderer
+            .render_to_buffer_hdr(gl, width, height, uniform_data, progress)
+            .map(Pixels::RgbaF32),
+        ExportDepth::Iterations => renderer
+            .render_iterations(gl, width, height, uniform_data, progress)
+            .map(Pixels::Iterations),
+        ExportDepth::SetMask => renderer
+            .render_set_mask(gl, width, height, uniform_data, progress)
+            .map(Pixels::Mask),
+    }
+}
+
+/// State of the screensaver-like mode that keeps zooming into a point.
+struct Demo {
+    /// Point being zoomed into, in fractal coordinates.
+    target: Vec2,
+    /// The view and colors from before the demo, which it starts over from and which are
+    /// restored when it stops.
+    start: UniformData,
+}
+
+impl Demo {
+    /// Puts the view and colors of `uniform_data` back to how they were before the demo.
+    fn restore(&self, uniform_data: &mut UniformData) {
+        uniform_data.center = self.start.center;
+        uniform_data.origin = self.start.origin;
+        uniform_data.zoom = self.start.zoom;
+        uniform_data.cycles = self.start.cycles;
+        uniform_data.start_color = self.start.start_color;
+        uniform_data.end_color = self.start.end_color;
+    }
+}
+
+/// How the pixels of a view ended up, reduced from their iteration counts.
+struct ViewStats {
+    /// Fraction of the pixels that neither escaped nor converged.
+    inside: f32,
+    /// Mean and largest iteration of the other pixels.
+    mean_escape: f32,
+    max_escape: u16,
+    /// Number of escaped pixels in equal parts of `0..cycles`.
+    histogram: [u32; HISTOGRAM_BINS],
+}
+
+impl ViewStats {
+    /// Reduces the counts from [`Renderer::render_iterations`], where `cycles` marks the pixels
+    /// inside the set.
+    fn new(iterations: &[u16], cycles: i32) -> Self {
+        let (mut inside, mut escaped, mut sum, mut max_escape) = (0, 0, 0, 0);
+        let mut histogram = [0; HISTOGRAM_BINS];
+        for &i in iterations {
+            if i32::from(i) >= cycles {
+                inside += 1;
+            } else {
+                escaped += 1;
+                sum += u64::from(i);
+                max_escape = max_escape.max(i);
+                let bin = usize::from(i) * HISTOGRAM_BINS / cycles.max(1) as usize;
+                histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+            }
+        }
         Self {
-            renderer: Arc::new(Mutex::new(Renderer::new(gl))),
+            inside: inside as f32 / iterations.len().max(1) as f32,
+            mean_escape: sum as f32 / escaped.max(1) as f32,
+            max_escape,
+            histogram,
+        }
+    }
+
+    /// Draws the statistics into the top right corner of `rect`, with the histogram of the
+    /// escape iterations below them.
+    fn show(&self, ui: &egui::Ui, rect: egui::Rect) {
+        let painter = ui.painter_at(rect);
+        let text = format!(
+            "In set: {:.2}%\nMean escape: {:.1}\nMax escape: {}",
+            self.inside * 100.,
+            self.mean_escape,
+            self.max_escape
+        );
+        let galley =
+            painter.layout_no_wrap(text, egui::FontId::default(), ui.visuals().text_color());
+        let size = vec2(
+            galley.size().x.max(2. * HISTOGRAM_BINS as f32),
+            galley.size().y + 48.,
+        );
+        let box_rect = egui::Align2::RIGHT_TOP.anchor_size(rect.right_top() + vec2(-8., 8.), size);
+        painter.rect_filled(
+            box_rect.expand(4.),
+            4.,
+            ui.visuals().extreme_bg_color.gamma_multiply(0.8),
+        );
+        painter.galley(box_rect.min, galley, ui.visuals().text_color());
+
+        // bars from 0 on the left to `cycles` on the right, scaled to the fullest one
+        let bars = egui::Rect::from_min_max(box_rect.left_bottom() - vec2(0., 40.), box_rect.max);
+        let fullest = self.histogram.iter().copied().max().unwrap_or(0).max(1);
+        let bar_width = bars.width() / HISTOGRAM_BINS as f32;
+        for (bin, &count) in self.histogram.iter().enumerate() {
+            let height = bars.height() * count as f32 / fullest as f32;
+            let left = bars.left() + bin as f32 * bar_width;
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(left, bars.bottom() - height),
+                    egui::pos2(left + bar_width, bars.bottom()),
+                ),
+                0.,
+                ui.visuals().text_color(),
+            );
+        }
+    }
+}
+
+/// Contents of the dialog that moves the view to typed coordinates, kept as text until
+/// they are confirmed.
+struct GoTo {
+    real: String,
+    imaginary: String,
+    magnification: String,
+    /// Fly to the point instead of jumping there.
+    animate: bool,
+    /// Why the entered values were rejected.
+    error: Option<String>,
+}
+
+/// Animation of the main view towards a point entered in the go-to dialog.
+struct Flight {
+    /// Target point in fractal coordinates, and the start relative to it.
+    to: [Fixed; 2],
+    from: Vec2,
+    from_zoom: f32,
+    to_zoom: f32,
+    /// Fraction of the way already travelled.
+    progress: f32,
+}
+
+/// Seconds the animated switch between fractal types takes.
+const MORPH_DURATION: f32 = 1.;
+
+/// Seconds a flight to a point from the go-to dialog takes.
+const FLIGHT_DURATION: f32 = 1.5;
+
+/// Distance of the middle of the view from `origin`, in view widths, past which deep zoom
+/// moves `origin` there. Floats keep offsets of this size precise, and a new reference orbit
+/// costs a frame, so panning doesn't compute one every frame.
+const REBASE_DISTANCE: f32 = 0.5;
+
+/// Like [`REBASE_DISTANCE`], but when the reference orbit escaped before `cycles`, in case
+/// the orbit of the point in the middle stays in longer.
+const ESCAPED_REBASE_DISTANCE: f32 = 0.05;
+
+/// Whether deep zoom should move `origin` to the middle of the view, see [`REBASE_DISTANCE`].
+fn needs_rebase(uniform_data: &UniformData, reference_escaped: bool) -> bool {
+    let distance = uniform_data.center.length();
+    distance > REBASE_DISTANCE || (reference_escaped && distance > ESCAPED_REBASE_DISTANCE)
+}
+
+/// Second fractal shown next to the main one for comparison.
+struct CompareView {
+    renderer: Arc<Mutex<Renderer>>,
+    uniform_data: UniformData,
+    fractal_type: FractalType,
+    /// Keep center and zoom the same in both views.
+    sync_navigation: bool,
+}
+
+/// Compiles the function of `fractal_type` into `renderer`, keeping the old program on error.
+fn set_fractal_type(
+    gl: &glow::Context,
+    renderer: &Mutex<Renderer>,
+    uniform_data: &mut UniformData,
+    fractal_type: FractalType,
+    custom_fractal_function: &str,
+) -> Result<(), ShaderError> {
+    uniform_data.convergent = fractal_type == FractalType::Newton;
+    renderer
+        .lock()
+        .set_fractal_type(gl, fractal_type, custom_fractal_function)
+}
+
+/// Lays out the custom function for the editor, with a background behind `line` (counting
+/// from 1), where the compiler found an error.
+fn highlight_line(
+    ui: &egui::Ui,
+    text: &str,
+    wrap_width: f32,
+    line: Option<u32>,
+) -> Arc<egui::Galley> {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let mut job = egui::text::LayoutJob::default();
+    for (i, text) in text.split_inclusive('\n').enumerate() {
+        let background = if line == Some(i as u32 + 1) {
+            ui.visuals().error_fg_color.gamma_multiply(0.3)
+        } else {
+            egui::Color32::TRANSPARENT
+        };
+        let format = egui::TextFormat {
+            font_id: font_id.clone(),
+            color: ui.visuals().text_color(),
+            background,
+            ..Default::default()
+        };
+        job.append(text, 0., format);
+    }
+    job.wrap.max_width = wrap_width;
+    ui.fonts(|fonts| fonts.layout_job(job))
+}
+
+/// Updates `region` (in fractions of `rect`) while the user shift-drags over the view,
+/// and outlines it.
+fn select_region(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    response: &egui::Response,
+    region: &mut Option<egui::Rect>,
+) {
+    let (shift, origin) = ui.input(|e| (e.modifiers.shift, e.pointer.press_origin()));
+    if let (true, true, Some(origin), Some(pointer)) = (
+        shift,
+        response.dragged(),
+        origin,
+        response.interact_pointer_pos(),
+    ) {
+        let selection = egui::Rect::from_two_pos(origin, pointer).intersect(rect);
+        *region = (selection.area() > 0.).then(|| {
+            egui::Rect::from_min_max(
+                ((selection.min - rect.min) / rect.size()).to_pos2(),
+                ((selection.max - rect.min) / rect.size()).to_pos2(),
+            )
+        });
+    }
+
+    if let Some(region) = region {
+        let outline = egui::Rect::from_min_max(
+            rect.min + region.min.to_vec2() * rect.size(),
+            rect.min + region.max.to_vec2() * rect.size(),
+        );
+        ui.painter().rect_stroke(
+            outline,
+            0.,
+            egui::Stroke::new(1., ui.visuals().selection.stroke.color),
+        );
+    }
+}
+
+/// Shows the point under the cursor and how its orbit ends next to the pointer.
+fn cursor_readout(
+    rect: egui::Rect,
+    response: &egui::Response,
+    uniform_data: &UniformData,
+    fractal_type: FractalType,
+) {
+    let Some(pointer) = response.hover_pos() else {
+        return;
+    };
+    let point = uniform_data.plane_point((pointer - rect.min) / rect.size());
+    let iterations = match orbit(fractal_type, uniform_data, point) {
+        Some(Orbit::Escaped(i) | Orbit::Converged(i)) => format!("{i} iterations"),
+        Some(Orbit::Bounded) => format!("∞ (in set, capped at {})", uniform_data.cycles),
+        None => "Iterations are only known for built-in fractals".to_string(),
+    };
+    response
+        .clone()
+        .on_hover_text_at_pointer(format!("{:.6}, {:.6}\n{iterations}", point.x, point.y));
+}
+
+/// Picks a random point just outside of the Mandelbrot set, whose Julia sets are the most
+/// intricate ones.
+fn random_julia_constant() -> Vec2 {
+    let uniform_data = UniformData {
+        cycles: 200,
+        ..Default::default()
+    };
+    let mut constant = Vec2::ZERO;
+    for _ in 0..1000 {
+        constant = vec2(
+            rand::random::<f32>() * 2.5 - 2.,
+            rand::random::<f32>() * 2.4 - 1.2,
+        );
+        // slow escapes only happen close to the boundary
+        if let Some(Orbit::Escaped(i)) = orbit(FractalType::Mandelbrot, &uniform_data, constant) {
+            if i >= 30 {
+                break;
+            }
+        }
+    }
+    constant
+}
+
+/// Sets the colors of `uniform_data` to those of `palette`, except the `locked` ones. A
+/// colormap or imported stops would hide a locked color, so with a lock only its ends are
+/// taken, as a gradient between two colors.
+fn apply_palette(uniform_data: &mut UniformData, palette: &Palette, locked: [bool; 2]) {
+    let [start_locked, end_locked] = locked;
+    uniform_data.colormap = palette.colormap.filter(|_| !start_locked && !end_locked);
+    uniform_data.gradient_stops = palette.stops.filter(|_| !start_locked && !end_locked);
+    if !start_locked {
+        uniform_data.start_color = palette.start_color;
+    }
+    if !end_locked {
+        uniform_data.end_color = palette.end_color;
+    }
+}
+
+/// Drag value for a part of a constant of custom functions. Dragging with Ctrl held is ten
+/// times coarser, with Shift ten times finer, which egui does by itself. Double-clicking it
+/// resets it to 0.
+fn constant_drag_value(ui: &mut egui::Ui, value: &mut f32, suffix: &str) {
+    let coarse = ui.input(|i| i.modifiers.command);
+    let response = ui
+        .add(
+            egui::DragValue::new(value)
+                .speed(if coarse { 0.01 } else { 0.001 })
+                .max_decimals(6)
+                .suffix(suffix),
+        )
+        .on_hover_text(
+            "Drag with Ctrl for coarse steps, with Shift for fine ones, double-click to reset to 0",
+        );
+    if response.double_clicked() {
+        *value = 0.;
+        // the first click started editing the text, which would put the old value back
+        response.surrender_focus();
+    }
+}
+
+/// Shows the Mandelbrot set with a marker at `constant`, which is moved to where the user
+/// clicks or drags. Julia sets of points inside the set are connected, the ones outside aren't.
+fn julia_inset(ui: &mut egui::Ui, renderer: &Arc<Mutex<Renderer>>, constant: &mut Vec2) {
+    let (rect, response) = ui.allocate_exact_size(vec2(150., 150.), Sense::click_and_drag());
+    let rect = ui.painter().round_rect_to_pixels(rect);
+    let ppp = ui.ctx().pixels_per_point();
+    let mut uniform_data = UniformData {
+        resolution: (rect.size() * ppp).round(),
+        window_offset: gl_window_offset(ui.ctx(), rect),
+        zoom: 0.35,
+        ..Default::default()
+    };
+    uniform_data.center_on(vec2(-0.6, 0.));
+
+    if let Some(pointer) = response.interact_pointer_pos() {
+        *constant = uniform_data.plane_point((pointer - rect.min) / rect.size());
+    }
+
+    paint_fractal(ui, rect, renderer, uniform_data);
+    let marker = rect.min + uniform_data.view_fraction(*constant) * rect.size();
+    ui.painter_at(rect).circle_stroke(
+        marker,
+        4.,
+        egui::Stroke::new(2., ui.visuals().selection.stroke.color),
+    );
+}
+
+/// Parses the go-to dialog field `name`, rejecting values that aren't finite.
+fn parse_number(text: &str, name: &str) -> Result<f32, String> {
+    let value: f32 = text
+        .trim()
+        .parse()
+        .map_err(|_| format!("{name} isn't a number"))?;
+    if !value.is_finite() {
+        return Err(format!("{name} must be finite"));
+    }
+    Ok(value)
+}
+
+/// Parses the go-to dialog coordinate `name` with all of its digits, which deep zooms need.
+fn parse_coordinate(text: &str, name: &str) -> Result<Fixed, String> {
+    text.parse()
+        .map_err(|_| format!("{name} isn't a decimal number"))
+}
+
+/// Functions the custom editor can start from, the examples followed by the builtin fractal
+/// types they don't cover.
+fn function_presets() -> Vec<(&'static str, &'static str)> {
+    let mut presets = EXAMPLE_FUNCS.to_vec();
+    for fractal_type in FractalType::ALL {
+        if let Some(function) = fractal_type.function() {
+            if !presets.iter().any(|&(_, preset)| preset == function) {
+                presets.push((fractal_type.name(), function));
+            }
+        }
+    }
+    presets
+}
+
+/// Formats `magnification` like "1,600,000×", rounded to three significant digits, since a
+/// float zoom isn't worth reading any closer.
+fn format_magnification(magnification: f32) -> String {
+    if magnification < 1. {
+        return format!("{}×", (magnification * 1000.).round() / 1000.);
+    }
+    // the zeros are appended, deep magnifications don't fit any integer
+    let shift = (magnification.log10().floor() as i32 - 2).max(0);
+    let significant = ((magnification as f64) / 10f64.powi(shift)).round() as u64;
+    let digits = significant.to_string() + &"0".repeat(shift as usize);
+    let mut text = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            text.push(',');
+        }
+        text.push(digit);
+    }
+    text + "×"
+}
+
+/// Formats the middle of the view and its magnification for pasting into a post or notes,
+/// with as many decimals as it takes to tell neighboring pixels apart.
+fn format_coordinates(uniform_data: &UniformData) -> String {
+    let [real, imaginary] = uniform_data.center_point();
+    let decimals = coordinate_decimals(uniform_data);
+    format!(
+        "re={real:.decimals$} im={imaginary:.decimals$} zoom={:.3e}",
+        uniform_data.magnification()
+    )
+}
+
+/// Decimals it takes to tell neighboring pixels of the view apart.
+fn coordinate_decimals(uniform_data: &UniformData) -> usize {
+    // the view is `1 / zoom` wide
+    let pixels_per_unit = uniform_data.zoom * uniform_data.resolution.x.max(1.);
+    pixels_per_unit.log10().ceil().max(0.) as usize + 1
+}
+
+impl GoTo {
+    /// Fills the dialog with the current view.
+    fn new(uniform_data: &UniformData) -> Self {
+        let [real, imaginary] = uniform_data.center_point();
+        let decimals = coordinate_decimals(uniform_data);
+        Self {
+            real: format!("{real:.decimals$}"),
+            imaginary: format!("{imaginary:.decimals$}"),
+            magnification: uniform_data.magnification().to_string(),
+            animate: false,
+            error: None,
+        }
+    }
+
+    /// Returns the entered point and the zoom showing it at the entered magnification.
+    fn parse(&self) -> Result<([Fixed; 2], f32), String> {
+        let real = parse_coordinate(&self.real, "Real part")?;
+        let imaginary = parse_coordinate(&self.imaginary, "Imaginary part")?;
+        let magnification = parse_number(&self.magnification, "Magnification")?;
+        let zoom = magnification * DEFAULT_ZOOM;
+        if !ZOOM_RANGE.contains(&zoom) {
+            return Err(format!(
+                "Magnification must be between {} and {}",
+                ZOOM_RANGE.start() / DEFAULT_ZOOM,
+                ZOOM_RANGE.end() / DEFAULT_ZOOM
+            ));
+        }
+        Ok(([real, imaginary], zoom))
+    }
+}
+
+/// Draws the lines through the origin of the plane along the real and imaginary axes over `rect`,
+/// and labels the origin.
+fn draw_axes(ui: &egui::Ui, rect: egui::Rect, uniform_data: &UniformData) {
+    let painter = ui.painter_at(rect);
+    let to_screen = |point: Vec2| rect.min + uniform_data.view_fraction(point) * rect.size();
+    let origin = to_screen(Vec2::ZERO);
+    // long enough to cross the whole view wherever the origin is
+    let length = (origin - rect.center()).length() + rect.size().length();
+    let stroke = egui::Stroke::new(1., ui.visuals().text_color());
+    for direction in [vec2(1., 0.), vec2(0., 1.)] {
+        let direction = (to_screen(direction) - origin).normalized() * length;
+        painter.line_segment([origin - direction, origin + direction], stroke);
+    }
+    painter.text(
+        origin + vec2(4., 2.),
+        egui::Align2::LEFT_TOP,
+        "0",
+        egui::FontId::default(),
+        ui.visuals().text_color(),
+    );
+}
+
+/// Smallest and largest escape iteration of the points of a coarse grid over the view,
+/// `None` for custom functions or when no point escapes.
+fn visible_iteration_range(
+    fractal_type: FractalType,
+    uniform_data: &UniformData,
+) -> Option<[i32; 2]> {
+    const SAMPLES: usize = 48;
+    let mut range: Option<[i32; 2]> = None;
+    for y in 0..SAMPLES {
+        for x in 0..SAMPLES {
+            let fraction = (vec2(x as f32, y as f32) + vec2(0.5, 0.5)) / SAMPLES as f32;
+            let point = uniform_data.plane_point(fraction);
+            if let Orbit::Escaped(i) = orbit(fractal_type, uniform_data, point)? {
+                range = Some(range.map_or([i, i], |[min, max]| [min.min(i), max.max(i)]));
+            }
+        }
+    }
+    range.map(|[min, max]| [min, max.max(min + 1)])
+}
+
+/// Collects the zoom `factor` for the view `id` and returns how many whole `step`s it adds up to,
+/// keeping the rest for later frames.
+fn zoom_steps(ctx: &egui::Context, id: egui::Id, factor: f32, step: f32) -> i32 {
+    ctx.data_mut(|data| {
+        let pending = data.get_temp_mut_or_default::<f32>(id.with("zoom_steps"));
+        *pending += factor.ln() / step.ln();
+        let steps = pending.trunc();
+        *pending -= steps;
+        steps as i32
+    })
+}
+
+/// Level of `zoom` when zooming in powers of `step`, rounded to the nearest one.
+fn zoom_level(zoom: f32, step: f32) -> i32 {
+    zoom.log(step).round() as i32
+}
+
+/// Factor moving `zoom` `steps` levels away from its nearest power of `step`.
+fn snapped_zoom(zoom: f32, step: f32, steps: i32) -> f32 {
+    step.powi(zoom_level(zoom, step) + steps) / zoom
+}
+
+/// Position of the bottom left corner of `rect` in physical pixels from the bottom left corner
+/// of the window, which is where `gl_FragCoord` starts.
+fn gl_window_offset(ctx: &egui::Context, rect: egui::Rect) -> Vec2 {
+    let ppp = ctx.pixels_per_point();
+    (vec2(rect.left(), ctx.screen_rect().bottom() - rect.bottom()) * ppp).round()
+}
+
+/// Shows `+` and `−` buttons in the top right corner of the view of `response`, which zoom
+/// about its middle, for touchscreens and anyone who can't scroll or pinch. Returns whether
+/// one was clicked.
+fn zoom_buttons(
+    ctx: &egui::Context,
+    response: &egui::Response,
+    uniform_data: &mut UniformData,
+    zoom_step: Option<f32>,
+) -> bool {
+    let size = vec2(28., 28.);
+    let corner = response.rect.right_top() + vec2(-8. - size.x, 8.);
+    egui::Area::new(response.id.with("zoom_buttons"))
+        .fixed_pos(corner)
+        .show(ctx, |ui| {
+            let mut clicked = false;
+            for (label, factor, steps) in [
+                ("+", BUTTON_ZOOM_FACTOR, 1),
+                ("−", 1. / BUTTON_ZOOM_FACTOR, -1),
+            ] {
+                let button = egui::Button::new(egui::RichText::new(label).size(18.));
+                if ui.add_sized(size, button).clicked() {
+                    let factor = match zoom_step {
+                        Some(step) => snapped_zoom(uniform_data.zoom, step, steps),
+                        None => factor,
+                    };
+                    let center = uniform_data.transform().anchor(vec2(0.5, 0.5));
+                    uniform_data.zoom_at(factor, center);
+                    clicked = true;
+                }
+            }
+            clicked
+        })
+        .inner
+}
+
+/// Pans and zooms `uniform_data` according to the input in `rect`, then returns what to paint
+/// there. `key_zoom` is the zoom factor requested with the keyboard for this view. With
+/// `zoom_step`, the zoom only moves between powers of it. With `scroll_zoom`, plain wheel
+/// scrolling zooms too, not just pinching and Ctrl+scroll. `max_cycles` limits the
+/// iterations painted, without changing `uniform_data`.
+#[allow(clippy::too_many_arguments)]
+fn fractal_view(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    response: &egui::Response,
+    uniform_data: &mut UniformData,
+    key_zoom: f32,
+    zoom_step: Option<f32>,
+    scroll_zoom: bool,
+    max_cycles: Option<i32>,
+) -> UniformData {
+    let ctx = ui.ctx();
+    // on whole physical pixels, so the edges of the view don't shimmer against the
+    // background at fractional scale factors
+    let rect = ui.painter().round_rect_to_pixels(rect);
+    let rect_size = rect.size();
+    // shift-drag selects an export region instead of panning
+    let drag = if ctx.input(|e| e.modifiers.shift) {
+        Vec2::ZERO
+    } else {
+        response.drag_delta() / rect_size
+    };
+
+    let ppp = ctx.pixels_per_point();
+
+    uniform_data.resolution = (rect_size * ppp).round();
+    uniform_data.window_offset = gl_window_offset(ctx, rect);
+    let mut transform = uniform_data.transform();
+    transform.pan(drag);
+    uniform_data.center = transform.center;
+
+    let view = uniform_data.transform();
+    let center = uniform_data.center;
+    let screen_to_fractal_coords = |pos: Pos2| view.anchor((pos - rect.min) / rect_size);
+
+    let (zoom, pointer) = ctx.input(|e| {
+        // egui already turns Ctrl+scroll into `zoom_delta`
+        let scroll = if scroll_zoom && !e.modifiers.command {
+            (e.raw_scroll_delta.y / SCROLL_ZOOM_POINTS).exp()
+        } else {
+            1.
+        };
+        (e.zoom_delta() * scroll, e.pointer.latest_pos())
+    });
+    let pointer = pointer.filter(|pointer| rect.contains(*pointer));
+    // a double-click or C centers the view on the point under the pointer, keeping the zoom
+    let recenter = response.double_clicked()
+        || (!ctx.wants_keyboard_input() && ctx.input(|e| e.key_pressed(egui::Key::C)));
+    if let (true, Some(pointer)) = (recenter, pointer) {
+        uniform_data.center = screen_to_fractal_coords(pointer);
+    }
+    if let Some(pointer) = pointer {
+        // only on frames that zoom, hovering mustn't snap the zoom or nudge the view
+        let zoom = match zoom_step {
+            Some(step) => match zoom_steps(ctx, response.id, zoom, step) {
+                0 => None,
+                steps => Some(snapped_zoom(uniform_data.zoom, step, steps)),
+            },
+            None => (zoom != 1.).then_some(zoom),
+        };
+        if let Some(zoom) = zoom {
+            uniform_data.zoom_at(zoom, screen_to_fractal_coords(pointer));
+        }
+    }
+
+    if key_zoom != 1. {
+        let key_zoom = match zoom_step {
+            Some(step) => snapped_zoom(uniform_data.zoom, step, if key_zoom > 1. { 1 } else { -1 }),
+            None => key_zoom,
+        };
+        let target = pointer.map_or(center, screen_to_fractal_coords);
+        uniform_data.zoom_at(key_zoom, target);
+    }
+
+    live_uniform_data(uniform_data, max_cycles)
+}
+
+/// What the live view paints of `uniform_data`, at most `max_cycles` iterations of it.
+fn live_uniform_data(uniform_data: &UniformData, max_cycles: Option<i32>) -> UniformData {
+    UniformData {
+        cycles: max_cycles.map_or(uniform_data.cycles, |max| uniform_data.cycles.min(max)),
+        // keep the colors of all the iterations while fewer are painted
+        color_range: Some(uniform_data.gradient_range()),
+        ..*uniform_data
+    }
+}
+
+/// What a screenshot of `uniform_data` renders, the selected `region` of it or all of it,
+/// scaled down to logical pixels when `pixels_per_point` is given. It's made from the
+/// configured view rather than the painted one, so it gets all the iterations even while the
+/// performance mode paints fewer.
+fn screenshot_uniform_data(
+    uniform_data: &UniformData,
+    region: Option<egui::Rect>,
+    pixels_per_point: Option<f32>,
+) -> UniformData {
+    let mut uniform_data = match region {
+        Some(region) => uniform_data.cropped(region),
+        None => *uniform_data,
+    };
+    // the view is rendered at native pixels, see `fractal_view`
+    if let Some(pixels_per_point) = pixels_per_point {
+        uniform_data.resolution = (uniform_data.resolution / pixels_per_point).round();
+    }
+    uniform_data
+}
+
+/// Paints `renderer` into `rect`, `uniform_data` needs the resolution and offset of `rect`.
+fn paint_fractal(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    renderer: &Arc<Mutex<Renderer>>,
+    uniform_data: UniformData,
+) {
+    let renderer = renderer.clone();
+    let callback = egui::PaintCallback {
+        rect,
+        callback: Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
+            use glow::HasContext as _;
+
+            let gl = painter.gl();
+            // egui's scissor box is the whole clip rect, narrow it to the view before clearing
+            let (view, clip) = (info.viewport_in_pixels(), info.clip_rect_in_pixels());
+            let left = view.left_px.max(clip.left_px);
+            let bottom = view.from_bottom_px.max(clip.from_bottom_px);
+            let right = (view.left_px + view.width_px).min(clip.left_px + clip.width_px);
+            let top =
+                (view.from_bottom_px + view.height_px).min(clip.from_bottom_px + clip.height_px);
+            unsafe {
+                gl.scissor(left, bottom, (right - left).max(0), (top - bottom).max(0));
+            }
+
+            let renderer = renderer.lock();
+            renderer.clear(gl, uniform_data);
+            renderer.paint(gl, uniform_data);
+        })),
+    };
+    ui.painter().add(callback);
+}
+
+/// Shown instead of the [`App`] when it can't start.
+struct StartupError {
+    message: String,
+}
+
+impl eframe::App for StartupError {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("FractalGUI can't start");
+            ui.label(&self.message);
+        });
+    }
+}
+
+impl App {
+    /// Creates the app, or a screen explaining why it can't run.
+    pub fn create(cc: &eframe::CreationContext<'_>) -> Box<dyn eframe::App> {
+        match Self::new(cc) {
+            Ok(app) => Box::new(app),
+            Err(message) => {
+                log::error!("{message}");
+                Box::new(StartupError { message })
+            }
+        }
+    }
+
+    /// Fails when eframe doesn't provide an OpenGL context, or when it's too old for the
+    /// renderer.
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Result<Self, String> {
+        let Some(gl) = cc.gl.as_ref() else {
+            return Err(
+                "The fractals are drawn with OpenGL, but no OpenGL context is \
+                 available. Make sure the graphics drivers support OpenGL 3.3, \
+                 or WebGL 2 in the browser."
+                    .to_string(),
+            );
+        };
+        let dark_mode = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, DARK_MODE_KEY))
+            .unwrap_or(true);
+        cc.egui_ctx.set_visuals(if dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
+        let (start_color, end_color) = default_colors(dark_mode);
+        let palettes = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, PALETTES_KEY))
+            .unwrap_or_else(builtin_palettes);
+        let max_frame_rate = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, FRAME_RATE_KEY))
+            .unwrap_or(None);
+        let scroll_zoom = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, SCROLL_ZOOM_KEY))
+            .unwrap_or(true);
+        let app = Self {
+            renderer: Arc::new(Mutex::new(Renderer::new(gl)?)),
             uniform_data: UniformData {
-                zoom: 0.2,
-                cycles: 100,
-                start_color: Hsva::new(1., 0., 1., 1.),
-                end_color: Hsva::new(0., 0., 0., 1.),
+                start_color,
+                end_color,
                 ..Default::default()
             },
-        }
+            fractal_type: FractalType::Mandelbrot,
+            custom_fractal_function: MANDELBROT_FUNC.trim().to_string(),
+            shader_error: None,
+            validation: None,
+            compile_time: None,
+            last_edit: None,
+            function_undo: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            shader_watcher: None,
+            screenshot_status: None,
+            export_job: None,
+            screenshot: None,
+            save_directory: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            numbered_screenshots: false,
+            export_depth: ExportDepth::Eight,
+            dark_mode,
+            settings_shown: true,
+            compare: None,
+            demo: None,
+            color_cycling: false,
+            color_cycle_speed: 0.1,
+            show_readout: false,
+            show_axes: false,
+            render_to_texture: false,
+            view_texture: None,
+            stats: None,
+            live_stats: false,
+            stats_time: 0.,
+            julia_inset: Arc::new(Mutex::new(Renderer::new(gl)?)),
+            morph_types: false,
+            morph: None,
+            period_checking: false,
+            deep_zoom: DeepZoom::Off,
+            performance_mode: false,
+            log_magnification: true,
+            max_frame_rate,
+            live_cycles: None,
+            palettes,
+            palette_name: String::new(),
+            locked_colors: [false; 2],
+            palette_error: None,
+            #[cfg(target_arch = "wasm32")]
+            palette_import: Default::default(),
+            snap_zoom: false,
+            scroll_zoom,
+            zoom_step: 2.,
+            export_logical_pixels: false,
+            export_samples: 1,
+            export_downsampling: Downsampling::Box,
+            export_transparent_interior: false,
+            export_region: None,
+            go_to: None,
+            flight: None,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let app = {
+            let mut app = app;
+            if let Some(settings) = read_startup_settings() {
+                if let Err(error) = settings.and_then(|settings| app.apply_settings(gl, settings)) {
+                    log::error!("{error}");
+                }
+            }
+            app
+        };
+        Ok(app)
     }
 }
 
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        egui::SidePanel::new(egui::panel::Side::Left, "side_panel").show(ctx, |ui| {
-            ui.heading("Settings");
-            ui.label("Iterations");
-            ui.add(Slider::new(&mut self.uniform_data.cycles, 1..=5000).logarithmic(true));
-            ui.separator();
-
-            ui.label("Start Color");
-            color_picker::color_edit_button_hsva(
-                ui,
-                &mut self.uniform_data.start_color,
-                color_picker::Alpha::Opaque,
+impl App {
+    /// Replaces the custom function with `preset`, or appends it commented out so the current
+    /// function still compiles, keeping the old text for "Undo insert".
+    fn insert_preset(&mut self, gl: &glow::Context, preset: &str, replace: bool) {
+        let preset = preset.trim();
+        let function = if replace {
+            preset.to_string()
+        } else {
+            let commented: Vec<String> = preset.lines().map(|line| format!("// {line}")).collect();
+            format!(
+                "{}\n\n{}",
+                self.custom_fractal_function,
+                commented.join("\n")
+            )
+        };
+        self.function_undo = Some(std::mem::replace(
+            &mut self.custom_fractal_function,
+            function,
+        ));
+        self.update_fractal_function(gl);
+    }
+
+    /// Compiles the function of the current fractal type, keeping the old program on error.
+    fn update_fractal_function(&mut self, gl: &glow::Context) {
+        let start = web_time::Instant::now();
+        self.shader_error = set_fractal_type(
+            gl,
+            &self.renderer,
+            &mut self.uniform_data,
+            self.fractal_type,
+            &self.custom_fractal_function,
+        )
+        .err();
+        // builtin programs are cached, so only custom ones take time worth showing
+        if self.fractal_type == FractalType::Custom {
+            self.compile_time = Some(start.elapsed());
+        }
+        self.validation = None;
+
+        if let Some(compare) = &mut self.compare {
+            // both views share the custom function
+            if compare.fractal_type == FractalType::Custom {
+                let compare_error = set_fractal_type(
+                    gl,
+                    &compare.renderer,
+                    &mut compare.uniform_data,
+                    compare.fractal_type,
+                    &self.custom_fractal_function,
+                )
+                .err();
+                self.shader_error = self.shader_error.take().or(compare_error);
+            }
+        }
+    }
+
+    /// The function of `fractal_type`, custom or builtin.
+    fn fractal_function(&self, fractal_type: FractalType) -> &str {
+        fractal_type
+            .function()
+            .unwrap_or(&self.custom_fractal_function)
+    }
+
+    /// Asks for the next frame of an animation, no sooner than `max_frame_rate` allows.
+    /// Without a running animation nothing asks, so the app idles until the next event.
+    fn request_animation_frame(&self, ctx: &egui::Context) {
+        match self.max_frame_rate {
+            Some(frame_rate) => {
+                ctx.request_repaint_after(std::time::Duration::from_secs_f32(1. / frame_rate))
+            }
+            None => ctx.request_repaint(),
+        }
+    }
+
+    /// Switches from `previous_type` to the current fractal type, animating it when enabled.
+    /// Without an animation, or if the combined program doesn't compile, it switches at once.
+    fn switch_fractal_type(&mut self, gl: &glow::Context, previous_type: FractalType) {
+        if self.morph_types {
+            let function = morph_function(
+                self.fractal_function(previous_type),
+                self.fractal_function(self.fractal_type),
             );
-            ui.separator();
+            if self
+                .renderer
+                .lock()
+                .set_fractal_function(gl, &function)
+                .is_ok()
+            {
+                self.morph = Some(0.);
+                self.uniform_data.morph = 0.;
+                return;
+            }
+        }
+        self.update_fractal_function(gl);
+    }
+
+    /// Advances the animated switch between fractal types, and compiles the program of the new
+    /// type once it's over.
+    fn update_morph(&mut self, ctx: &egui::Context, gl: &glow::Context) {
+        let Some(progress) = &mut self.morph else {
+            return;
+        };
+        *progress = (*progress + ctx.input(|i| i.stable_dt) / MORPH_DURATION).min(1.);
+        self.uniform_data.morph = *progress * *progress * (3. - 2. * *progress);
+        if *progress >= 1. {
+            self.morph = None;
+            self.uniform_data.morph = 0.;
+            self.update_fractal_function(gl);
+        } else {
+            self.request_animation_frame(ctx);
+        }
+    }
+
+    /// Starts zooming into `target` (in fractal coordinates) from the current view.
+    fn start_demo(&mut self, target: Vec2) {
+        self.demo = Some(Demo {
+            target,
+            start: self.uniform_data,
+        });
+    }
+
+    /// Advances the demo by one frame, stopping it on any user input.
+    fn update_demo(&mut self, ctx: &egui::Context) {
+        let Some(demo) = &self.demo else {
+            return;
+        };
+
+        let (interrupted, dt) = ctx.input(|i| {
+            let interrupted = i.pointer.any_pressed()
+                || !i.keys_down.is_empty()
+                || i.raw_scroll_delta != Vec2::ZERO
+                || i.zoom_delta() != 1.;
+            (interrupted, i.stable_dt)
+        });
+        if interrupted {
+            demo.restore(&mut self.uniform_data);
+            self.demo = None;
+            return;
+        }
+
+        let uniform_data = &mut self.uniform_data;
+        let center = uniform_data.center;
+        uniform_data.zoom_at(DEMO_ZOOM_SPEED.powf(dt), center);
+        // glide towards the target, through `origin` once deep zoom moved the view into it
+        let middle = uniform_data.plane_point(vec2(0.5, 0.5));
+        uniform_data.center_on(middle + (demo.target - middle) * (2. * dt).min(1.));
+
+        let depth = (uniform_data.zoom / demo.start.zoom).log2().max(0.);
+        uniform_data.cycles = ((demo.start.cycles as f32 * (1. + 0.3 * depth)) as i32).min(5000);
+        for color in [&mut uniform_data.start_color, &mut uniform_data.end_color] {
+            color.h = (color.h + 0.02 * dt).fract();
+        }
+
+        if uniform_data.zoom > DEMO_MAX_ZOOM {
+            let (start_color, end_color) = (uniform_data.start_color, uniform_data.end_color);
+            demo.restore(uniform_data);
+            // keep cycling the hue from where it is
+            uniform_data.start_color = start_color;
+            uniform_data.end_color = end_color;
+            self.demo = Some(Demo {
+                target: DEMO_POINTS[rand::random::<usize>() % DEMO_POINTS.len()],
+                ..*demo
+            });
+        }
+        self.request_animation_frame(ctx);
+    }
+
+    /// Reads back the iteration counts of the main view, scaled down to [`STATS_SIZE`], and
+    /// reduces them to [`ViewStats`].
+    fn compute_stats(&mut self, gl: &glow::Context) {
+        self.stats = self
+            .renderer
+            .lock()
+            .render_preview_iterations(gl, STATS_SIZE, self.uniform_data)
+            .ok()
+            .map(|(_, iterations)| ViewStats::new(&iterations, self.uniform_data.cycles));
+    }
+
+    /// Computes the statistics again while they are live and due, reading the iterations
+    /// back only every [`LIVE_STATS_INTERVAL`].
+    fn update_live_stats(&mut self, ctx: &egui::Context, gl: &glow::Context) {
+        if !self.live_stats {
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        if now - self.stats_time >= LIVE_STATS_INTERVAL {
+            self.compute_stats(gl);
+            self.stats_time = now;
+        }
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(LIVE_STATS_INTERVAL));
+    }
+
+    /// Shows the go-to dialog while it's open, and moves the view once it's confirmed.
+    fn go_to_ui(&mut self, ctx: &egui::Context) {
+        let Some(go_to) = &mut self.go_to else {
+            return;
+        };
+
+        let mut open = true;
+        let mut cancelled = false;
+        let mut confirmed = false;
+        egui::Window::new("Go to")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Grid::new("go_to_fields").show(ui, |ui| {
+                    for (label, text) in [
+                        ("Real", &mut go_to.real),
+                        ("Imaginary", &mut go_to.imaginary),
+                        ("Magnification", &mut go_to.magnification),
+                    ] {
+                        ui.label(label);
+                        let field = ui.text_edit_singleline(text);
+                        confirmed |=
+                            field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        ui.end_row();
+                    }
+                });
+                ui.checkbox(&mut go_to.animate, "Animate");
+                ui.horizontal(|ui| {
+                    confirmed |= ui.button("Go").clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+                if let Some(error) = &go_to.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+            });
+
+        if confirmed {
+            match go_to.parse() {
+                Ok((point, zoom)) => {
+                    if let Some(demo) = self.demo.take() {
+                        demo.restore(&mut self.uniform_data);
+                    }
+                    if go_to.animate {
+                        let [real, imaginary] = self.uniform_data.center_point();
+                        let from = vec2(
+                            (real - point[0]).to_f64() as f32,
+                            (imaginary - point[1]).to_f64() as f32,
+                        );
+                        self.uniform_data.center_near(point, from);
+                        self.flight = Some(Flight {
+                            to: point,
+                            from,
+                            from_zoom: self.uniform_data.zoom,
+                            to_zoom: zoom,
+                            progress: 0.,
+                        });
+                    } else {
+                        self.flight = None;
+                        self.uniform_data.zoom = zoom;
+                        self.uniform_data.center_near(point, Vec2::ZERO);
+                    }
+                    open = false;
+                }
+                Err(error) => go_to.error = Some(error),
+            }
+        }
+        if !open || cancelled {
+            self.go_to = None;
+        }
+    }
+
+    /// Advances the flight to a point from the go-to dialog, stopping it when the user pans
+    /// or zooms.
+    fn update_flight(&mut self, ctx: &egui::Context) {
+        let Some(flight) = &mut self.flight else {
+            return;
+        };
+
+        let (interrupted, dt) = ctx.input(|i| {
+            let interrupted =
+                i.pointer.any_pressed() || i.raw_scroll_delta != Vec2::ZERO || i.zoom_delta() != 1.;
+            (interrupted, i.stable_dt)
+        });
+        if interrupted {
+            self.flight = None;
+            return;
+        }
+
+        flight.progress = (flight.progress + dt / FLIGHT_DURATION).min(1.);
+        // ease in and out, and change the zoom exponentially so it feels steady
+        let t = flight.progress * flight.progress * (3. - 2. * flight.progress);
+        let zoom = flight.from_zoom * (flight.to_zoom / flight.from_zoom).powf(t);
+        self.uniform_data.zoom = zoom;
+        self.uniform_data
+            .center_near(flight.to, flight.from * (1. - t));
+
+        if flight.progress >= 1. {
+            self.flight = None;
+        } else {
+            self.request_animation_frame(ctx);
+        }
+    }
+
+    /// Renders `uniform_data` into `view_texture` and draws it into `rect`, painting directly
+    /// instead when the render fails.
+    fn paint_view_texture(
+        &mut self,
+        ui: &egui::Ui,
+        gl: &glow::Context,
+        rect: egui::Rect,
+        uniform_data: UniformData,
+    ) {
+        let (width, height) = (
+            uniform_data.resolution.x as u32,
+            uniform_data.resolution.y as u32,
+        );
+        let pixels =
+            self.renderer
+                .lock()
+                .render_to_buffer(gl, width, height, uniform_data, &mut |_| {});
+        let pixels = match pixels {
+            Ok(pixels) => pixels,
+            Err(error) => {
+                log::warn!("Can't render the view into a texture: {error}");
+                paint_fractal(ui, rect, &self.renderer, uniform_data);
+                return;
+            }
+        };
+        let image =
+            egui::ColorImage::from_rgba_unmultiplied([width as usize, height as usize], &pixels);
+        // the texture has one texel per physical pixel of `rect`
+        let options = egui::TextureOptions::NEAREST;
+        let texture = match &mut self.view_texture {
+            Some(texture) => {
+                texture.set(image, options);
+                texture
+            }
+            texture @ None => texture.insert(ui.ctx().load_texture("view", image, options)),
+        };
+        ui.painter().image(
+            texture.id(),
+            rect,
+            egui::Rect::from_min_max(Pos2::ZERO, egui::pos2(1., 1.)),
+            egui::Color32::WHITE,
+        );
+    }
+
+    /// Keeps the view near `UniformData::origin` while deep zoom is active, with an up to
+    /// date reference orbit while it's perturbed, and moves it back into `center` otherwise.
+    fn update_reference_orbit(&mut self, gl: &glow::Context) {
+        if self.uniform_data.perturbation || self.uniform_data.double_float {
+            let mut renderer = self.renderer.lock();
+            let escaped = self.uniform_data.perturbation && renderer.reference_escaped();
+            if needs_rebase(&self.uniform_data, escaped) {
+                self.uniform_data.rebase();
+            }
+            if self.uniform_data.perturbation {
+                renderer.update_reference_orbit(gl, &self.uniform_data);
+            }
+        } else {
+            self.uniform_data.flatten_origin();
+        }
+    }
+
+    /// Shows settings of the second view, creating or destroying it as it's toggled.
+    fn compare_ui(&mut self, ui: &mut egui::Ui, gl: &glow::Context) {
+        let mut enabled = self.compare.is_some();
+        ui.checkbox(&mut enabled, "Compare side by side");
+        match (enabled, &mut self.compare) {
+            (true, None) => {
+                let renderer = match Renderer::new(gl) {
+                    Ok(renderer) => Arc::new(Mutex::new(renderer)),
+                    Err(error) => {
+                        self.shader_error = Some(error.into());
+                        return;
+                    }
+                };
+                let mut uniform_data = self.uniform_data;
+                self.shader_error = set_fractal_type(
+                    gl,
+                    &renderer,
+                    &mut uniform_data,
+                    self.fractal_type,
+                    &self.custom_fractal_function,
+                )
+                .err();
+                self.compare = Some(CompareView {
+                    renderer,
+                    uniform_data,
+                    fractal_type: self.fractal_type,
+                    sync_navigation: true,
+                });
+            }
+            (false, Some(compare)) => {
+                compare.renderer.lock().destroy(gl);
+                self.compare = None;
+            }
+            (true, Some(compare)) => {
+                ui.horizontal(|ui| {
+                    ui.label("Right view");
+                    let mut fractal_type = compare.fractal_type;
+                    egui::ComboBox::from_id_source("compare_fractal_type")
+                        .selected_text(fractal_type.name())
+                        .show_ui(ui, |ui| {
+                            for option in FractalType::ALL {
+                                ui.selectable_value(&mut fractal_type, option, option.name());
+                            }
+                        });
+                    if fractal_type != compare.fractal_type {
+                        compare.fractal_type = fractal_type;
+                        self.shader_error = set_fractal_type(
+                            gl,
+                            &compare.renderer,
+                            &mut compare.uniform_data,
+                            fractal_type,
+                            &self.custom_fractal_function,
+                        )
+                        .err();
+                    }
+                });
+                ui.checkbox(&mut compare.sync_navigation, "Sync navigation");
+                if ui
+                    .button("Swap sides")
+                    .on_hover_text("The settings above apply to the left view")
+                    .clicked()
+                {
+                    std::mem::swap(&mut self.renderer, &mut compare.renderer);
+                    std::mem::swap(&mut self.uniform_data, &mut compare.uniform_data);
+                    std::mem::swap(&mut self.fractal_type, &mut compare.fractal_type);
+                }
+            }
+            (false, None) => {}
+        }
+    }
+
+    /// Lets the user pick a file to load the custom function from, and reloads it on changes.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn shader_watcher_ui(&mut self, ui: &mut egui::Ui, frame: &eframe::Frame) {
+        match &mut self.shader_watcher {
+            Some(watcher) => {
+                ui.label(format!("Watching {}", watcher.path.display()));
+                if let Some(contents) = watcher.poll() {
+                    match contents {
+                        Ok(contents) => match sanitize_fractal_function(&contents) {
+                            Ok(function) => {
+                                self.custom_fractal_function = function;
+                                self.update_fractal_function(frame.gl().unwrap());
+                            }
+                            Err(error) => self.shader_error = Some(error.into()),
+                        },
+                        Err(error) => self.shader_error = Some(error.into()),
+                    }
+                }
+                if ui.button("Stop watching").clicked() {
+                    self.shader_watcher = None;
+                }
+                // there are no input events when the file changes
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_millis(500));
+            }
+            None => {
+                if ui.button("Watch file").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("GLSL", &["glsl", "frag"])
+                        .pick_file()
+                    {
+                        self.shader_watcher = Some(FileWatcher::new(path));
+                    }
+                }
+            }
+        }
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            fractal_type: self.fractal_type,
+            custom_fractal_function: self.custom_fractal_function.clone(),
+            uniform_data: self.uniform_data,
+        }
+    }
+
+    /// Restores `settings`, failing without changing anything if their custom function
+    /// can't be compiled, see [`sanitize_fractal_function`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_settings(&mut self, gl: &glow::Context, settings: Settings) -> Result<(), String> {
+        let custom_fractal_function = sanitize_fractal_function(&settings.custom_fractal_function)?;
+        // only rebuild the shader when the restored function differs from the active one
+        let recompile = settings.fractal_type != self.fractal_type
+            || (settings.fractal_type == FractalType::Custom
+                && custom_fractal_function != self.custom_fractal_function);
+        self.uniform_data = UniformData {
+            resolution: self.uniform_data.resolution,
+            window_offset: self.uniform_data.window_offset,
+            convergent: self.uniform_data.convergent,
+            period_checking: self.uniform_data.period_checking,
+            perturbation: self.uniform_data.perturbation,
+            double_float: self.uniform_data.double_float,
+            ..settings.uniform_data
+        };
+        self.uniform_data.zoom = self
+            .uniform_data
+            .zoom
+            .clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end());
+        self.fractal_type = settings.fractal_type;
+        self.custom_fractal_function = custom_fractal_function;
+        if recompile {
+            self.update_fractal_function(gl);
+        }
+        Ok(())
+    }
+
+    /// Restores the view from the metadata of an image picked by the user.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_from_image(&mut self, gl: &glow::Context) -> Result<String, String> {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .pick_file()
+        else {
+            return Ok("Loading cancelled".to_string());
+        };
+        let metadata = read_metadata(&path, METADATA_KEY)?;
+        let settings = serde_json::from_str(&metadata)
+            .map_err(|e| format!("Invalid view in {}: {e}", path.display()))?;
+        self.apply_settings(gl, settings)
+            .map_err(|e| format!("Invalid view in {}: {e}", path.display()))?;
+        Ok(format!("Loaded view from {}", path.display()))
+    }
 
-            ui.label("End Color");
-            color_picker::color_edit_button_hsva(
-                ui,
-                &mut self.uniform_data.end_color,
-                color_picker::Alpha::Opaque,
+    /// Asks where to save the complete fragment shader of the current fractal, as compiled.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_shader(&self) -> Result<String, String> {
+        let function = self
+            .fractal_type
+            .function()
+            .unwrap_or(&self.custom_fractal_function);
+        let source = fragment_shader_source(function)?;
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("fractal.frag")
+            .add_filter("GLSL", &["glsl", "frag"])
+            .save_file()
+        else {
+            return Ok("Saving cancelled".to_string());
+        };
+        std::fs::write(&path, source)
+            .map_err(|e| format!("Cannot write {}: {e}", path.display()))?;
+        Ok(format!("Saved {}", path.display()))
+    }
+
+    /// Saves a screenshot with a numbered name when enabled, or asks where to save it.
+    fn save_screenshot(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &Pixels,
+        metadata: &str,
+    ) -> Result<String, String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (true, Some(directory)) = (self.numbered_screenshots, &self.save_directory) {
+            // PNG would clamp the colors of HDR exports
+            let extension = match self.export_depth {
+                ExportDepth::Float => "exr",
+                _ => "png",
+            };
+            return save_image_numbered(
+                directory,
+                extension,
+                width,
+                height,
+                pixels,
+                (METADATA_KEY, metadata),
             );
-            ui.separator();
+        }
+        save_image(
+            &mut self.save_directory,
+            width,
+            height,
+            pixels,
+            (METADATA_KEY, metadata),
+        )
+    }
+
+    /// Starts rendering the main view (or the selected region of it), see [`ExportJob`].
+    fn take_screenshot(&mut self, ctx: &egui::Context) {
+        if self.export_job.is_some() {
+            return;
+        }
+        let uniform_data = screenshot_uniform_data(
+            &self.uniform_data,
+            self.export_region,
+            self.export_logical_pixels.then(|| ctx.pixels_per_point()),
+        );
+
+        let (width, height) = (
+            uniform_data.resolution.x as u32,
+            uniform_data.resolution.y as u32,
+        );
+        if width == 0 || height == 0 {
+            self.screenshot_status = Some(Err("There's nothing on screen to capture".to_string()));
+            return;
+        }
+        let max_size = self.renderer.lock().max_texture_size();
+        if width.max(height) > max_size {
+            self.screenshot_status = Some(Err(format!(
+                "The image is larger than the {max_size} px texture limit"
+            )));
+            return;
+        }
+        // averaging iteration counts would make them meaningless
+        let wanted_samples = match self.export_depth {
+            ExportDepth::Iterations => 1,
+            _ => self.export_samples,
+        };
+        let samples = wanted_samples.min(max_size / width.max(height));
+        let render_data = UniformData {
+            resolution: uniform_data.resolution * samples as f32,
+            transparent_interior: self.export_transparent_interior,
+            ..uniform_data
+        };
+        self.export_job = Some(ExportJob {
+            uniform_data,
+            render_data,
+            depth: self.export_depth,
+            samples,
+            wanted_samples,
+            pixels: None,
+            rows_done: 0,
+            started: ctx.input(|i| i.time),
+        });
+    }
 
-            if ui.button("Take screenshot").clicked() {
-                let renderer = self.renderer.clone();
-                let uniform_data = self.uniform_data.clone();
+    /// Renders the next band of the running export, and saves the image once it's complete.
+    fn update_export(&mut self, ctx: &egui::Context, gl: &glow::Context) {
+        let Some(job) = &mut self.export_job else {
+            return;
+        };
+        let renderer = self.renderer.lock();
+        let (render_width, render_height) = job.render_size();
+        let rows = RENDER_STRIP_HEIGHT.min(render_height - job.rows_done);
+        let band = egui::Rect::from_x_y_ranges(
+            0.0..=1.,
+            job.rows_done as f32 / render_height as f32
+                ..=(job.rows_done + rows) as f32 / render_height as f32,
+        );
+        let band = render_export(
+            gl,
+            &renderer,
+            job.depth,
+            render_width,
+            rows,
+            job.render_data.cropped(band),
+        );
+        let band = match band {
+            Ok(band) => band,
+            Err(error) => {
+                drop(renderer);
+                self.export_job = None;
+                self.screenshot_status = Some(Err(error));
+                return;
+            }
+        };
+        match &mut job.pixels {
+            Some(pixels) => pixels.append(band),
+            None => job.pixels = Some(band),
+        }
+        job.rows_done += rows;
+        log::debug!("Screenshot {:.0}% rendered", job.progress() * 100.);
+        if job.rows_done < render_height {
+            ctx.request_repaint();
+            return;
+        }
+        let max_size = renderer.max_texture_size();
+        drop(renderer);
+
+        let Some(job) = self.export_job.take() else {
+            return;
+        };
+        let (width, height) = (
+            job.uniform_data.resolution.x as u32,
+            job.uniform_data.resolution.y as u32,
+        );
+        let output = job
+            .pixels
+            .expect("A finished export has at least one band")
+            .downsample(width, height, job.samples, self.export_downsampling);
+        debug_assert_eq!(
+            output.pixel_count(),
+            width as usize * height as usize,
+            "The exported image should have the requested size"
+        );
+        let metadata = serde_json::to_string(&Settings {
+            uniform_data: job.uniform_data,
+            ..self.settings()
+        })
+        .expect("Settings should always serialize");
+        self.screenshot_status = Some(self.save_screenshot(width, height, &output, &metadata));
+        if job.samples < job.wanted_samples {
+            if let Some(Ok(message)) = &mut self.screenshot_status {
+                message.push_str(&format!(
+                    ", at {}× quality to fit the {max_size} px texture limit",
+                    job.samples
+                ));
+            }
+        }
 
-                let (width, height) = (
-                    uniform_data.resolution.x as u32,
-                    uniform_data.resolution.y as u32,
+        // drawn again at thumbnail size rather than scaling the export down, data layers show
+        // the colors they were taken from
+        let preview = self.renderer.lock().render_preview(
+            gl,
+            THUMBNAIL_SIZE,
+            UniformData {
+                output_layer: OutputLayer::Color,
+                ..job.render_data
+            },
+        );
+        self.screenshot = match preview {
+            Ok(([preview_width, preview_height], preview)) => {
+                let image = egui::ColorImage::from_rgba_unmultiplied(
+                    [preview_width as usize, preview_height as usize],
+                    &preview,
                 );
-                let output = renderer.lock().render_to_buffer(
-                    frame.gl().unwrap(),
+                Some(Screenshot {
                     width,
                     height,
-                    uniform_data,
-                );
-                let mut file = File::create("./output.ppm").unwrap();
-                writeln!(file, "P6").unwrap();
-                println!("{} {}", width, height);
-                writeln!(file, "{} {}", width, height).unwrap();
-                writeln!(file, "255").unwrap();
-                for rgba in output.chunks_exact(4) {
-                    file.write(&rgba[..3]).unwrap();
+                    pixels: output,
+                    metadata,
+                    preview: ctx.load_texture("screenshot", image, egui::TextureOptions::LINEAR),
+                })
+            }
+            Err(error) => {
+                log::warn!("Can't render the screenshot thumbnail: {error}");
+                None
+            }
+        };
+    }
+
+    /// Covers the app with the progress of the running export and a button to cancel it.
+    fn export_overlay(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.export_job else {
+            return;
+        };
+        let progress = job.progress();
+        let elapsed = ctx.input(|i| i.time) - job.started;
+        let screen = ctx.screen_rect();
+        let mut cancelled = false;
+        egui::Area::new(egui::Id::new("export_overlay"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(screen.min)
+            .show(ctx, |ui| {
+                // takes the input meant for the app below
+                ui.allocate_rect(screen, Sense::click_and_drag());
+                ui.painter()
+                    .rect_filled(screen, 0., egui::Color32::from_black_alpha(160));
+                let dialog = egui::Rect::from_center_size(screen.center(), vec2(260., 100.));
+                ui.allocate_ui_at_rect(dialog, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label("Rendering the screenshot");
+                        ui.add(egui::ProgressBar::new(progress).show_percentage());
+                        // the bands take about the same time, so the rest takes as long per band
+                        if progress > 0. {
+                            let remaining = elapsed * (1. - progress as f64) / progress as f64;
+                            ui.label(format!("About {:.0} s left", remaining.ceil()));
+                        }
+                        cancelled = ui.button("Cancel").clicked();
+                    });
+                });
+            });
+        if cancelled {
+            self.export_job = None;
+            self.screenshot_status = Some(Err("The screenshot was cancelled".to_string()));
+        }
+    }
+
+    /// Adjusts the iterations of the live view to the last frame time in the performance mode.
+    fn update_live_cycles(&mut self, ctx: &egui::Context) {
+        let cycles = self.uniform_data.cycles;
+        if !self.performance_mode {
+            self.live_cycles = None;
+            return;
+        }
+        let frame_time = ctx.input(|i| i.unstable_dt);
+        // long gaps are the app idling between events, not slow frames
+        if frame_time > 0.5 {
+            return;
+        }
+
+        let live_cycles = self.live_cycles.unwrap_or(cycles) as f32;
+        let live_cycles = if frame_time > SLOW_FRAME_TIME {
+            live_cycles * 0.8
+        } else if frame_time < FAST_FRAME_TIME {
+            (live_cycles * 1.1).ceil()
+        } else {
+            live_cycles
+        };
+        let live_cycles = (live_cycles as i32).clamp(MIN_LIVE_CYCLES.min(cycles), cycles);
+        self.live_cycles = (live_cycles < cycles).then_some(live_cycles);
+    }
+
+    /// Applies and saves named gradients.
+    fn palette_ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Palette")
+            .selected_text(&self.palette_name)
+            .show_ui(ui, |ui| {
+                let colormaps = Colormap::ALL.map(Palette::from_colormap);
+                for (i, palette) in self.palettes.iter().chain(&colormaps).enumerate() {
+                    if i == self.palettes.len() {
+                        ui.separator();
+                        ui.weak("Color-blind friendly");
+                    }
+                    if ui
+                        .selectable_label(palette.name == self.palette_name, &palette.name)
+                        .clicked()
+                    {
+                        apply_palette(&mut self.uniform_data, palette, self.locked_colors);
+                        self.palette_name = palette.name.clone();
+                    }
                 }
-            };
+            });
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.palette_name).desired_width(100.));
+            let name = self.palette_name.trim().to_string();
+            let existing = self.palettes.iter().position(|p| p.name == name);
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save"))
+                .on_hover_text("Save the current colors under this name")
+                .clicked()
+            {
+                let palette = Palette {
+                    name,
+                    start_color: self.uniform_data.start_color,
+                    end_color: self.uniform_data.end_color,
+                    colormap: self.uniform_data.colormap,
+                    stops: self.uniform_data.gradient_stops,
+                };
+                match existing {
+                    Some(i) => self.palettes[i] = palette,
+                    None => self.palettes.push(palette),
+                }
+            }
+            if let Some(i) = existing {
+                if ui.button("Delete").clicked() {
+                    self.palettes.remove(i);
+                }
+            }
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let (fractal_rect, response) =
-                ui.allocate_exact_size((500., 500.).into(), Sense::drag());
-            let rect_size = fractal_rect.size();
-            let drag = response.drag_delta() / rect_size;
-
-            let ppp = ctx.pixels_per_point();
-
-            self.uniform_data.resolution = (rect_size * ppp).into();
-            self.uniform_data.window_offset = (fractal_rect.left_top() * ppp).to_vec2();
-            self.uniform_data.center -= drag;
-
-            let center = self.uniform_data.center;
-            let mut window_correction =
-                ctx.screen_rect().left_bottom() - fractal_rect.left_bottom();
-            window_correction.x *= -1.;
-            let screen_to_fractal_coords = |pos: Pos2| {
-                let pos = (pos.to_vec2() - window_correction) / rect_size;
-                let pos = pos - vec2(0.5, 0.5);
-                pos + center
+        if ui
+            .button("Import GIMP palette")
+            .on_hover_text("Uses all the colors of a .gpl or .ggr file as a gradient")
+            .clicked()
+        {
+            self.import_palette(ui.ctx());
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let imported = self.palette_import.lock().take();
+            if let Some(result) = imported {
+                self.palette_error = result.map(|palette| self.add_palette(palette)).err();
+            }
+        }
+        if let Some(error) = &self.palette_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+
+    /// Adds `palette` to the library, replacing one with the same name, and applies it.
+    fn add_palette(&mut self, palette: Palette) {
+        apply_palette(&mut self.uniform_data, &palette, self.locked_colors);
+        self.palette_name = palette.name.clone();
+        match self.palettes.iter().position(|p| p.name == palette.name) {
+            Some(i) => self.palettes[i] = palette,
+            None => self.palettes.push(palette),
+        }
+    }
+
+    /// Asks for a GIMP palette or gradient file and adds it to the library.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn import_palette(&mut self, _ctx: &egui::Context) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("GIMP palette", &["gpl", "ggr"])
+            .pick_file()
+        else {
+            return;
+        };
+        let fallback_name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot read {}: {e}", path.display()))
+            .and_then(|contents| parse_gimp(&contents, &fallback_name));
+        self.palette_error = result.map(|palette| self.add_palette(palette)).err();
+    }
+
+    /// Lets the user upload a GIMP palette or gradient file, which is added to the library
+    /// on a later frame.
+    #[cfg(target_arch = "wasm32")]
+    fn import_palette(&mut self, ctx: &egui::Context) {
+        let slot = self.palette_import.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .add_filter("GIMP palette", &["gpl", "ggr"])
+                .pick_file()
+                .await
+            else {
+                return;
             };
+            let name = file.file_name();
+            let fallback_name = name
+                .rsplit_once('.')
+                .map_or(name.as_str(), |(stem, _)| stem);
+            let result = String::from_utf8(file.read().await)
+                .map_err(|_| format!("{name} isn't a text file"))
+                .and_then(|contents| parse_gimp(&contents, fallback_name));
+            *slot.lock() = Some(result);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Switches the UI theme, also flipping the gradient if it's still at the old theme's defaults.
+    fn set_dark_mode(&mut self, ctx: &egui::Context, dark_mode: bool) {
+        let colors = (self.uniform_data.start_color, self.uniform_data.end_color);
+        if colors == default_colors(self.dark_mode) {
+            (self.uniform_data.start_color, self.uniform_data.end_color) =
+                default_colors(dark_mode);
+        }
+        ctx.set_visuals(if dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
+        self.dark_mode = dark_mode;
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if !ctx.wants_keyboard_input()
+            && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape))
+        {
+            self.settings_shown = !self.settings_shown;
+        }
+        if !ctx.wants_keyboard_input()
+            && ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::S))
+        {
+            self.take_screenshot(ctx);
+        }
+        if !ctx.wants_keyboard_input()
+            && ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::G))
+        {
+            self.go_to = Some(GoTo::new(&self.uniform_data));
+        }
+        self.go_to_ui(ctx);
+        self.update_export(ctx, frame.gl().unwrap());
+        self.export_overlay(ctx);
+        self.update_flight(ctx);
+        self.update_morph(ctx, frame.gl().unwrap());
+        self.update_demo(ctx);
+        self.update_live_cycles(ctx);
+        self.update_live_stats(ctx, frame.gl().unwrap());
+        if self.color_cycling {
+            let dt = ctx.input(|i| i.stable_dt);
+            let offset = &mut self.uniform_data.color_offset;
+            *offset = (*offset + self.color_cycle_speed * dt).fract();
+            self.request_animation_frame(ctx);
+        }
+
+        // the panel width is remembered in egui's memory, which eframe persists
+        egui::SidePanel::new(egui::panel::Side::Left, "side_panel")
+            .resizable(true)
+            .width_range(150.0..=ctx.screen_rect().width() / 2.)
+            .show_animated(ctx, self.settings_shown, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Settings");
+                    if ui
+                        .button("Hide")
+                        .on_hover_text("Toggle with Escape")
+                        .clicked()
+                    {
+                        self.settings_shown = false;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Theme");
+                    let mut dark_mode = self.dark_mode;
+                    ui.selectable_value(&mut dark_mode, true, "Dark");
+                    ui.selectable_value(&mut dark_mode, false, "Light");
+                    if dark_mode != self.dark_mode {
+                        self.set_dark_mode(ctx, dark_mode);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut limited = self.max_frame_rate.is_some();
+                    ui.checkbox(&mut limited, "Limit frame rate").on_hover_text(
+                        "Repaint animations less often, which saves power on fast screens",
+                    );
+                    let mut frame_rate = self.max_frame_rate.unwrap_or(30.);
+                    ui.add_enabled(
+                        limited,
+                        egui::DragValue::new(&mut frame_rate)
+                            .range(1.0..=240.0)
+                            .suffix(" fps"),
+                    );
+                    self.max_frame_rate = limited.then_some(frame_rate);
+                });
+                ui.separator();
+
+                ui.label("Fractal");
+                for fractal_type in FractalType::ALL {
+                    let previous_type = self.fractal_type;
+                    // `changed` is only set when a different type is picked,
+                    // so clicking the active preset keeps the current program
+                    if ui
+                        .radio_value(&mut self.fractal_type, fractal_type, fractal_type.name())
+                        .changed()
+                    {
+                        self.switch_fractal_type(frame.gl().unwrap(), previous_type);
+                    }
+                }
+                ui.checkbox(&mut self.morph_types, "Animate switching")
+                    .on_hover_text("Blend the formulas of the old and the new fractal over a second");
+                if ui
+                    .button("Random Julia set")
+                    .on_hover_text("Show the Julia set of a point near the edge of the Mandelbrot set")
+                    .clicked()
+                {
+                    self.uniform_data.constants[0] = random_julia_constant();
+                    self.uniform_data.blend = 0.;
+                    self.fractal_type = FractalType::Custom;
+                    let gl = frame.gl().unwrap();
+                    if self.custom_fractal_function == BLENDED_JULIA_FUNC.trim() {
+                        self.update_fractal_function(gl);
+                    } else {
+                        // like the presets, the edited function can be brought back
+                        self.insert_preset(gl, BLENDED_JULIA_FUNC, true);
+                    }
+                }
+                if self.fractal_type == FractalType::Mandelbrot {
+                    ui.checkbox(&mut self.period_checking, "Period checking")
+                        .on_hover_text(
+                            "Stop early on points whose orbit cycles, much faster inside the set \
+                             but it can change the edge slightly",
+                        );
+                    let double_float_supported = self.renderer.lock().double_float_supported();
+                    egui::ComboBox::from_label("Deep zoom")
+                        .selected_text(self.deep_zoom.name())
+                        .show_ui(ui, |ui| {
+                            for deep_zoom in DeepZoom::ALL {
+                                ui.add_enabled_ui(
+                                    deep_zoom != DeepZoom::DoubleFloat || double_float_supported,
+                                    |ui| {
+                                        ui.selectable_value(
+                                            &mut self.deep_zoom,
+                                            deep_zoom,
+                                            deep_zoom.name(),
+                                        )
+                                        .on_disabled_hover_text(
+                                            "The graphics driver doesn't compute them exactly",
+                                        );
+                                    },
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Past where single precision runs out, either iterate in pairs of \
+                             floats, which goes about a thousand times deeper, or relative to \
+                             one orbit computed in fixed point, which goes as deep as the zoom \
+                             does but is slower. Only in the single view",
+                        );
+                }
+                if self.fractal_type == FractalType::Newton {
+                    ui.label("Polynomial degree");
+                    ui.add(Slider::new(&mut self.uniform_data.newton_degree, 2..=8));
+                }
+                if self.fractal_type == FractalType::Phoenix {
+                    ui.label("p");
+                    ui.add(Slider::new(&mut self.uniform_data.phoenix_p, -1.0..=1.0));
+                }
+                if self.fractal_type != FractalType::Newton {
+                    let seed = &mut self.uniform_data.z_seed;
+                    ui.horizontal(|ui| {
+                        ui.label("Initial z");
+                        ui.add(egui::DragValue::new(&mut seed.x).speed(0.001));
+                        ui.add(egui::DragValue::new(&mut seed.y).speed(0.001).suffix("i"));
+                    })
+                    .response
+                    .on_hover_text(
+                        "Where the orbit starts instead of 0, read by custom functions as z_seed",
+                    );
+                }
+                if self.fractal_type == FractalType::Custom {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("example_function")
+                            .selected_text("Insert preset")
+                            .show_ui(ui, |ui| {
+                                for (name, function) in function_presets() {
+                                    ui.horizontal(|ui| {
+                                        if ui
+                                            .small_button("Replace")
+                                            .on_hover_text("Replace the function with this one")
+                                            .clicked()
+                                        {
+                                            self.insert_preset(frame.gl().unwrap(), function, true);
+                                        }
+                                        if ui
+                                            .small_button("Append")
+                                            .on_hover_text(
+                                                "Add this function commented out below the \
+                                                 current one, to copy parts of it",
+                                            )
+                                            .clicked()
+                                        {
+                                            self.insert_preset(frame.gl().unwrap(), function, false);
+                                        }
+                                        ui.label(name);
+                                    });
+                                }
+                            });
+                        if let Some(undo) = &self.function_undo {
+                            if ui
+                                .button("Undo insert")
+                                .on_hover_text("Go back to the function from before the last preset")
+                                .clicked()
+                            {
+                                self.custom_fractal_function = undo.clone();
+                                self.function_undo = None;
+                                self.update_fractal_function(frame.gl().unwrap());
+                            }
+                        }
+                    });
+                    egui::CollapsingHeader::new("Constants").show(ui, |ui| {
+                        let [constant_a, constant_b] = &mut self.uniform_data.constants;
+                        for (name, constant) in [("a", constant_a), ("b", constant_b)] {
+                            ui.horizontal(|ui| {
+                                ui.label(name);
+                                constant_drag_value(ui, &mut constant.x, "");
+                                constant_drag_value(ui, &mut constant.y, "i");
+                            });
+                        }
+                        ui.add(Slider::new(&mut self.uniform_data.blend, 0.0..=1.0).text("blend"));
+                        julia_inset(ui, &self.julia_inset, &mut self.uniform_data.constants[0]);
+                        ui.label("Click the Mandelbrot set to move a");
+                    })
+                    .header_response
+                    .on_hover_text("Read by custom functions as constant_a, constant_b and blend");
+                    ui.label("vec2 iteration(vec2 previous_z, vec2 original_z)");
+                    // the editor contents are known to be fine once validated
+                    let error_line = match &self.validation {
+                        Some(result) => result.as_ref().err().and_then(|error| error.line),
+                        None => self.shader_error.as_ref().and_then(|error| error.line),
+                    };
+                    let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                        highlight_line(ui, text, wrap_width, error_line)
+                    };
+                    let editor = ui.add(
+                        egui::TextEdit::multiline(&mut self.custom_fractal_function)
+                            .code_editor()
+                            .desired_width(f32::INFINITY)
+                            .layouter(&mut layouter),
+                    );
+                    let now = ctx.input(|i| i.time);
+                    if editor.changed() {
+                        self.last_edit = Some(now);
+                        self.validation = None;
+                        // the text from before the preset would lose the edit
+                        self.function_undo = None;
+                    }
+                    if let Some(last_edit) = self.last_edit {
+                        if now - last_edit > VALIDATION_DELAY {
+                            let start = web_time::Instant::now();
+                            self.validation = Some(self.renderer.lock().validate_fractal_function(
+                                frame.gl().unwrap(),
+                                &self.custom_fractal_function,
+                            ));
+                            self.compile_time = Some(start.elapsed());
+                            self.last_edit = None;
+                        } else {
+                            ctx.request_repaint_after(std::time::Duration::from_secs_f64(
+                                VALIDATION_DELAY,
+                            ));
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Compile").clicked() {
+                            self.update_fractal_function(frame.gl().unwrap());
+                        }
+                        match &self.validation {
+                            Some(Ok(())) => {
+                                ui.colored_label(egui::Color32::GREEN, "OK");
+                            }
+                            Some(Err(error)) => {
+                                let text = match error.line {
+                                    Some(line) => format!("Error on line {line}"),
+                                    None => "Error".to_string(),
+                                };
+                                ui.colored_label(ui.visuals().error_fg_color, text);
+                            }
+                            None => {}
+                        }
+                        if let Some(compile_time) = self.compile_time {
+                            ui.weak(format!("{} ms", compile_time.as_millis())).on_hover_text(
+                                "How long compiling and linking the function took last time, \
+                                 slow compiles hint at a costly shader",
+                            );
+                        }
+                    });
+                    if let Some(Err(error)) = &self.validation {
+                        ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+                    }
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    self.shader_watcher_ui(ui, frame);
+                }
+                if let Some(error) = &self.shader_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+                }
+                ui.separator();
+
+                self.compare_ui(ui, frame.gl().unwrap());
+                ui.separator();
+
+                ui.label("Iterations");
+                ui.add(Slider::new(&mut self.uniform_data.cycles, 1..=5000).logarithmic(true));
+                ui.checkbox(&mut self.performance_mode, "Performance mode")
+                    .on_hover_text("Use fewer iterations in the view while frames are slow");
+                if let Some(live_cycles) = self.live_cycles {
+                    ui.label(format!("The view uses {live_cycles}, screenshots all of them"));
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Magnification");
+                    ui.weak(format_magnification(self.uniform_data.magnification()))
+                        .on_hover_text("How many times larger than the initial view");
+                    ui.checkbox(&mut self.log_magnification, "Logarithmic")
+                        .on_hover_text("Magnify by the same factor with every step of the slider");
+                });
+                let mut magnification = self.uniform_data.magnification();
+                let slider = Slider::new(
+                    &mut magnification,
+                    ZOOM_RANGE.start() / DEFAULT_ZOOM..=ZOOM_RANGE.end() / DEFAULT_ZOOM,
+                )
+                .logarithmic(self.log_magnification)
+                .suffix("×");
+                if ui.add(slider).changed() {
+                    // about the middle of the view, like the zoom buttons
+                    let factor = magnification * DEFAULT_ZOOM / self.uniform_data.zoom;
+                    self.uniform_data.zoom_at(factor, self.uniform_data.center);
+                }
+                ui.separator();
+
+                ui.label("Rotation");
+                ui.add(Slider::new(&mut self.uniform_data.rotation, -180.0..=180.0).suffix("°"));
+                ui.separator();
+
+                ui.label("Coloring");
+                egui::ComboBox::from_id_source("coloring_mode")
+                    .selected_text(self.uniform_data.coloring_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in ColoringMode::ALL {
+                            ui.selectable_value(
+                                &mut self.uniform_data.coloring_mode,
+                                mode,
+                                mode.name(),
+                            );
+                        }
+                    });
+                ui.add_enabled_ui(
+                    self.uniform_data.coloring_mode == ColoringMode::EscapeTime,
+                    |ui| {
+                        egui::ComboBox::from_label("Smoothing")
+                            .selected_text(self.uniform_data.smoothing.name())
+                            .show_ui(ui, |ui| {
+                                for smoothing in Smoothing::ALL {
+                                    ui.selectable_value(
+                                        &mut self.uniform_data.smoothing,
+                                        smoothing,
+                                        smoothing.name(),
+                                    );
+                                }
+                            });
+                        let mut fixed_range = self.uniform_data.color_range.is_some();
+                        if ui
+                            .checkbox(&mut fixed_range, "Fixed color range")
+                            .on_hover_text(
+                                "Map the gradient to these escape iterations \
+                                 instead of all of them, so raising the iterations only \
+                                 adds detail without recoloring the view",
+                            )
+                            .changed()
+                        {
+                            self.uniform_data.color_range =
+                                fixed_range.then_some([0, self.uniform_data.cycles]);
+                        }
+                        if let Some([min, max]) = &mut self.uniform_data.color_range {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::DragValue::new(min).range(0..=*max - 1));
+                                ui.label("to");
+                                ui.add(egui::DragValue::new(max).range(*min + 1..=i32::MAX));
+                            });
+                        }
+                        let fit = ui
+                            .add_enabled(
+                                self.fractal_type != FractalType::Custom,
+                                egui::Button::new("Fit to view"),
+                            )
+                            .on_hover_text("Span the gradient over the escape iterations visible")
+                            .on_disabled_hover_text(
+                                "Iterations are only known for built-in fractals",
+                            );
+                        if fit.clicked() {
+                            if let Some(range) =
+                                visible_iteration_range(self.fractal_type, &self.uniform_data)
+                            {
+                                self.uniform_data.color_range = Some(range);
+                            }
+                        }
+                    },
+                );
+                ui.label("Color bands");
+                ui.add(Slider::new(&mut self.uniform_data.color_bands, 0..=64));
+                ui.label("Color offset");
+                ui.horizontal(|ui| {
+                    ui.add(Slider::new(&mut self.uniform_data.color_offset, 0.0..=1.0))
+                        .on_hover_text("Shift where the gradient starts");
+                    ui.checkbox(&mut self.uniform_data.cyclic_gradient, "Repeat")
+                        .on_hover_text(
+                            "Start the gradient over past its end, so the offset wraps around",
+                        );
+                });
+                ui.checkbox(&mut self.uniform_data.dither, "Dither")
+                    .on_hover_text("Hide banding in smooth gradients with a little noise");
+                egui::ComboBox::from_label("Antialiasing")
+                    .selected_text(self.uniform_data.antialiasing.name())
+                    .show_ui(ui, |ui| {
+                        for antialiasing in Antialiasing::ALL {
+                            ui.selectable_value(
+                                &mut self.uniform_data.antialiasing,
+                                antialiasing,
+                                antialiasing.name(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Smooth the jagged edge of the set. Derivative blends pixels where the \
+                         escape count jumps with their neighbors and is nearly free, supersample \
+                         computes four points per pixel and is four times as slow",
+                    );
+                egui::CollapsingHeader::new("Grading").show(ui, |ui| {
+                    egui::Grid::new("grading").show(ui, |ui| {
+                        ui.label("");
+                        ui.label("Brightness");
+                        ui.label("Contrast");
+                        ui.end_row();
+                        let uniform_data = &mut self.uniform_data;
+                        for (channel, name) in ["Red", "Green", "Blue"].into_iter().enumerate() {
+                            ui.label(name);
+                            ui.add(
+                                egui::DragValue::new(&mut uniform_data.brightness[channel])
+                                    .range(-1.0..=1.0)
+                                    .speed(0.005),
+                            );
+                            ui.add(
+                                egui::DragValue::new(&mut uniform_data.contrast[channel])
+                                    .range(0.0..=3.0)
+                                    .speed(0.005),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                    if ui.button("Reset").clicked() {
+                        self.uniform_data.brightness = [0.; 3];
+                        self.uniform_data.contrast = [1.; 3];
+                    }
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    // cycling runs the offset through the gradient, which only wraps smoothly
+                    // when the gradient repeats
+                    if ui.checkbox(&mut self.color_cycling, "Cycle colors").changed()
+                        && self.color_cycling
+                    {
+                        self.uniform_data.cyclic_gradient = true;
+                    }
+                    ui.add_enabled(
+                        self.color_cycling,
+                        Slider::new(&mut self.color_cycle_speed, 0.01..=1.0)
+                            .logarithmic(true)
+                            .text("speed"),
+                    );
+                });
+                ui.separator();
+
+                self.palette_ui(ui);
+                ui.separator();
+
+                let replaced_by = match (self.uniform_data.gradient_stops, self.uniform_data.colormap)
+                {
+                    (Some(_), _) => Some("the imported gradient"),
+                    (None, Some(colormap)) => Some(colormap.name()),
+                    (None, None) => None,
+                };
+                if let Some(name) = replaced_by {
+                    ui.label(format!(
+                        "Colored with {name}, changing a color goes back to a gradient between them",
+                    ));
+                }
+                ui.label("Start Color");
+                ui.horizontal(|ui| {
+                    if color_picker::color_edit_button_hsva(
+                        ui,
+                        &mut self.uniform_data.start_color,
+                        color_picker::Alpha::Opaque,
+                    )
+                    .changed()
+                    {
+                        self.uniform_data.colormap = None;
+                        self.uniform_data.gradient_stops = None;
+                    }
+                    ui.checkbox(&mut self.locked_colors[0], "Lock")
+                        .on_hover_text("Keep this color when applying a palette");
+                });
+                ui.separator();
+
+                ui.label("End Color");
+                ui.horizontal(|ui| {
+                    if color_picker::color_edit_button_hsva(
+                        ui,
+                        &mut self.uniform_data.end_color,
+                        color_picker::Alpha::Opaque,
+                    )
+                    .changed()
+                    {
+                        self.uniform_data.colormap = None;
+                        self.uniform_data.gradient_stops = None;
+                    }
+                    ui.checkbox(&mut self.locked_colors[1], "Lock")
+                        .on_hover_text("Keep this color when applying a palette");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Blend in");
+                    for interpolation in Interpolation::ALL {
+                        ui.selectable_value(
+                            &mut self.uniform_data.interpolation,
+                            interpolation,
+                            interpolation.name(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("HSV sweeps through the hues between the colors, RGB blends straight");
+                if ui
+                    .button("Reset colors")
+                    .on_hover_text("Go back to the default start and end colors")
+                    .clicked()
+                {
+                    (self.uniform_data.start_color, self.uniform_data.end_color) =
+                        default_colors(self.dark_mode);
+                    self.uniform_data.colormap = None;
+                    self.uniform_data.gradient_stops = None;
+                }
+                ui.separator();
+
+                ui.label("Background Color");
+                color_picker::color_edit_button_hsva(
+                    ui,
+                    &mut self.uniform_data.background_color,
+                    color_picker::Alpha::Opaque,
+                );
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Demo");
+                    if ui
+                        .button("Zoom here")
+                        .on_hover_text("Keep zooming into the current center, stops on any input")
+                        .clicked()
+                    {
+                        self.start_demo(self.uniform_data.plane_point(vec2(0.5, 0.5)));
+                    }
+                    if ui.button("Random").clicked() {
+                        let target = DEMO_POINTS[rand::random::<usize>() % DEMO_POINTS.len()];
+                        self.start_demo(target);
+                    }
+                });
+                ui.separator();
 
-            ctx.input(|e| {
-                let zoom = e.zoom_delta();
-                if let Some(pointer) = e.pointer.latest_pos() {
-                    let pointer = screen_to_fractal_coords(pointer);
-                    self.uniform_data.zoom *= zoom;
-                    self.uniform_data.center += pointer * (zoom - 1.);
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Go to…")
+                        .on_hover_text("Enter the coordinates to show\nShortcut: Ctrl+G")
+                        .clicked()
+                    {
+                        self.go_to = Some(GoTo::new(&self.uniform_data));
+                    }
+                    if ui
+                        .button("Whole fractal")
+                        .on_hover_text("Frame the interesting part of the fractal")
+                        .clicked()
+                    {
+                        self.uniform_data.fit(self.fractal_type.framing());
+                    }
+                    if ui
+                        .button("Copy coordinates")
+                        .on_hover_text(
+                            "Copy the middle of the view and the magnification as text, \
+                             like re=-0.74 im=0.13 zoom=1.000e2",
+                        )
+                        .clicked()
+                    {
+                        ui.ctx().copy_text(format_coordinates(&self.uniform_data));
+                    }
+                });
+                ui.checkbox(&mut self.show_readout, "Cursor readout")
+                    .on_hover_text("Show the point and iteration count under the cursor");
+                ui.checkbox(&mut self.show_axes, "Axes");
+                if ui
+                    .checkbox(&mut self.render_to_texture, "Render through a texture")
+                    .on_hover_text(
+                        "Show the view as an ordinary image, so what's drawn over it \
+                         composites like over the rest of the UI. Slower, only for the single \
+                         view",
+                    )
+                    .changed()
+                    && !self.render_to_texture
+                {
+                    self.view_texture = None;
+                }
+                // the counts are read back from a 16-bit texture, which WebGL can't render to
+                if cfg!(not(target_arch = "wasm32")) {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Compute stats")
+                            .on_hover_text("Count the pixels of the view in and out of the set")
+                            .clicked()
+                        {
+                            self.compute_stats(frame.gl().unwrap());
+                        }
+                        ui.checkbox(&mut self.live_stats, "Live").on_hover_text(
+                            "Update the stats twice a second, which reads the whole view back",
+                        );
+                        if self.stats.is_some() && ui.button("Hide stats").clicked() {
+                            self.stats = None;
+                            self.live_stats = false;
+                        }
+                    });
+                }
+                ui.checkbox(&mut self.scroll_zoom, "Scroll to zoom")
+                    .on_hover_text("Zoom with the mouse wheel, otherwise only with Ctrl+scroll");
+                ui.checkbox(&mut self.snap_zoom, "Zoom in steps");
+                if self.snap_zoom {
+                    ui.add(
+                        Slider::new(&mut self.zoom_step, 1.1..=10.)
+                            .text("Step")
+                            .suffix("×"),
+                    );
+                    ui.label(format!(
+                        "Zoom level {}",
+                        zoom_level(self.uniform_data.zoom, self.zoom_step)
+                    ));
+                }
+                ui.separator();
+
+                // WebGL can't render into 16-bit or float textures
+                if cfg!(not(target_arch = "wasm32")) {
+                    ui.horizontal(|ui| {
+                        for depth in ExportDepth::ALL {
+                            ui.selectable_value(&mut self.export_depth, depth, depth.name());
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "HDR keeps unclamped colors, use it with .exr\n\
+                         Iterations saves the escape count of each pixel as .png or .npy\n\
+                         Mask saves the set in white on transparent, smoothed by the export quality",
+                    );
+                }
+                ui.add_enabled(
+                    matches!(
+                        self.export_depth,
+                        ExportDepth::Eight | ExportDepth::Sixteen | ExportDepth::Float
+                    ),
+                    egui::Checkbox::new(
+                        &mut self.export_transparent_interior,
+                        "Transparent interior",
+                    ),
+                )
+                .on_hover_text(
+                    "Leave the set transparent in screenshots, to put them over other \
+                     backgrounds. PPM has no alpha channel and stays opaque",
+                );
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.export_logical_pixels, false, "Native pixels");
+                    ui.selectable_value(&mut self.export_logical_pixels, true, "Logical pixels");
+                })
+                .response
+                .on_hover_text(format!(
+                    "This display has {} native pixels per logical one",
+                    ctx.pixels_per_point()
+                ));
+                ui.add(
+                    Slider::new(&mut self.export_samples, 1..=8)
+                        .text("Export quality")
+                        .suffix("×"),
+                )
+                .on_hover_text(format!(
+                    "Renders screenshots this many times larger and scales them down, \
+                     up to {} px",
+                    self.renderer.lock().max_texture_size()
+                ));
+                ui.add_enabled_ui(self.export_samples > 1, |ui| {
+                    egui::ComboBox::from_label("Downsampling")
+                        .selected_text(self.export_downsampling.name())
+                        .show_ui(ui, |ui| {
+                            for filter in Downsampling::ALL {
+                                ui.selectable_value(
+                                    &mut self.export_downsampling,
+                                    filter,
+                                    filter.name(),
+                                );
+                            }
+                        });
+                })
+                .response
+                .on_hover_text(
+                    "Box is smooth, Lanczos sharper with slight halos, \
+                     Nearest keeps the aliasing",
+                );
+                if ui
+                    .button("Take screenshot")
+                    .on_hover_text(
+                        "Shortcut: Ctrl+S\nShift-drag over the view to export just a part of it",
+                    )
+                    .clicked()
+                {
+                    self.take_screenshot(ctx);
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.numbered_screenshots, "Save without asking")
+                        .on_hover_text("Name screenshots fractal_0001.png, fractal_0002.png, …");
+                    if self.numbered_screenshots {
+                        let folder = match &self.save_directory {
+                            Some(directory) => directory.display().to_string(),
+                            None => "Choose folder".to_string(),
+                        };
+                        if ui.button(folder).clicked() {
+                            if let Some(directory) = rfd::FileDialog::new().pick_folder() {
+                                self.save_directory = Some(directory);
+                            }
+                        }
+                    }
+                });
+                if self.export_region.is_some() && ui.button("Clear selection").clicked() {
+                    self.export_region = None;
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui
+                    .button("Load from image")
+                    .on_hover_text("Restore the view saved in an exported PNG")
+                    .clicked()
+                {
+                    self.screenshot_status = Some(self.load_from_image(frame.gl().unwrap()));
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui
+                    .button("Save shader")
+                    .on_hover_text("Save the complete fragment shader of the fractal as compiled")
+                    .clicked()
+                {
+                    self.screenshot_status = Some(self.save_shader());
+                }
+                match &self.screenshot_status {
+                    Some(Ok(message)) => {
+                        ui.label(message);
+                    }
+                    Some(Err(error)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                    None => {}
+                }
+                if let Some(screenshot) = &self.screenshot {
+                    let preview = egui::Image::new(&screenshot.preview)
+                        .max_size(vec2(ui.available_width(), 150.))
+                        .sense(Sense::click());
+                    if ui
+                        .add(preview)
+                        .on_hover_text("Click to save again")
+                        .clicked()
+                    {
+                        self.screenshot_status = Some(save_image(
+                            &mut self.save_directory,
+                            screenshot.width,
+                            screenshot.height,
+                            &screenshot.pixels,
+                            (METADATA_KEY, &screenshot.metadata),
+                        ));
+                    }
                 }
             });
 
-            let renderer = self.renderer.clone();
-            let uniform_data = self.uniform_data.clone();
+        if !self.settings_shown {
+            egui::Area::new(egui::Id::new("open_settings"))
+                .anchor(egui::Align2::LEFT_TOP, (8., 8.))
+                .show(ctx, |ui| {
+                    if ui
+                        .button("Settings")
+                        .on_hover_text("Toggle with Escape")
+                        .clicked()
+                    {
+                        self.settings_shown = true;
+                    }
+                });
+        }
 
-            let callback = egui::PaintCallback {
-                rect: fractal_rect,
-                callback: Arc::new(egui_glow::CallbackFn::new(move |_, painter| {
-                    renderer.lock().paint(painter.gl(), uniform_data);
-                })),
+        let deep = self.fractal_type == FractalType::Mandelbrot
+            && self.morph.is_none()
+            && self.compare.is_none()
+            && self.uniform_data.z_seed == Vec2::ZERO
+            && self.uniform_data.zoom > DEEP_ZOOM_START;
+        self.uniform_data.perturbation = deep && self.deep_zoom == DeepZoom::Perturbation;
+        self.uniform_data.double_float = deep
+            && self.deep_zoom == DeepZoom::DoubleFloat
+            && self.renderer.lock().double_float_supported();
+        self.update_reference_orbit(frame.gl().unwrap());
+        // only the Mandelbrot orbit is known to depend on the current iterate alone, and
+        // the deep iterates aren't precise enough to compare
+        self.uniform_data.period_checking = self.period_checking
+            && self.fractal_type == FractalType::Mandelbrot
+            && !self.uniform_data.perturbation
+            && !self.uniform_data.double_float;
+        if let Some(compare) = &mut self.compare {
+            compare.uniform_data.period_checking =
+                self.period_checking && compare.fractal_type == FractalType::Mandelbrot;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let key_zoom = if ctx.wants_keyboard_input() {
+                1.
+            } else {
+                ctx.input(|e| {
+                    let zoom_in =
+                        e.key_pressed(egui::Key::Plus) || e.key_pressed(egui::Key::Equals);
+                    let zoom_out = e.key_pressed(egui::Key::Minus);
+                    match (zoom_in, zoom_out) {
+                        (true, false) => KEY_ZOOM_FACTOR,
+                        (false, true) => 1. / KEY_ZOOM_FACTOR,
+                        _ => 1.,
+                    }
+                })
             };
-            ui.painter().add(callback);
+
+            let zoom_step = self.snap_zoom.then_some(self.zoom_step);
+            match &mut self.compare {
+                None => {
+                    let (fractal_rect, response) =
+                        ui.allocate_exact_size((500., 500.).into(), Sense::click_and_drag());
+                    zoom_buttons(ctx, &response, &mut self.uniform_data, zoom_step);
+                    let uniform_data = fractal_view(
+                        ui,
+                        fractal_rect,
+                        &response,
+                        &mut self.uniform_data,
+                        key_zoom,
+                        zoom_step,
+                        self.scroll_zoom,
+                        self.live_cycles,
+                    );
+                    if self.render_to_texture {
+                        self.paint_view_texture(
+                            ui,
+                            frame.gl().unwrap(),
+                            fractal_rect,
+                            uniform_data,
+                        );
+                    } else {
+                        paint_fractal(ui, fractal_rect, &self.renderer, uniform_data);
+                    }
+                    select_region(ui, fractal_rect, &response, &mut self.export_region);
+                    if self.show_axes {
+                        draw_axes(ui, fractal_rect, &self.uniform_data);
+                    }
+                    if let Some(stats) = &self.stats {
+                        stats.show(ui, fractal_rect);
+                    }
+                    if self.show_readout {
+                        cursor_readout(
+                            fractal_rect,
+                            &response,
+                            &self.uniform_data,
+                            self.fractal_type,
+                        );
+                    }
+                }
+                Some(compare) => {
+                    let (left_rect, left_response, right_rect, right_response) = ui
+                        .horizontal(|ui| {
+                            let (left_rect, left_response) = ui
+                                .allocate_exact_size((250., 500.).into(), Sense::click_and_drag());
+                            let (right_rect, right_response) = ui
+                                .allocate_exact_size((250., 500.).into(), Sense::click_and_drag());
+                            (left_rect, left_response, right_rect, right_response)
+                        })
+                        .inner;
+
+                    zoom_buttons(ctx, &left_response, &mut self.uniform_data, zoom_step);
+                    let right_zoomed =
+                        zoom_buttons(ctx, &right_response, &mut compare.uniform_data, zoom_step);
+                    // keyboard zoom goes to the hovered view, or to the left one
+                    let right_active =
+                        right_response.hovered() || right_response.dragged() || right_zoomed;
+                    let (left_key_zoom, right_key_zoom) = if right_active {
+                        (1., key_zoom)
+                    } else {
+                        (key_zoom, 1.)
+                    };
+                    let uniform_data = fractal_view(
+                        ui,
+                        left_rect,
+                        &left_response,
+                        &mut self.uniform_data,
+                        left_key_zoom,
+                        zoom_step,
+                        self.scroll_zoom,
+                        self.live_cycles,
+                    );
+                    paint_fractal(ui, left_rect, &self.renderer, uniform_data);
+                    select_region(ui, left_rect, &left_response, &mut self.export_region);
+                    if self.show_axes {
+                        draw_axes(ui, left_rect, &self.uniform_data);
+                    }
+                    if let Some(stats) = &self.stats {
+                        stats.show(ui, left_rect);
+                    }
+                    if self.show_readout {
+                        cursor_readout(
+                            left_rect,
+                            &left_response,
+                            &self.uniform_data,
+                            self.fractal_type,
+                        );
+                        cursor_readout(
+                            right_rect,
+                            &right_response,
+                            &compare.uniform_data,
+                            compare.fractal_type,
+                        );
+                    }
+                    let uniform_data = fractal_view(
+                        ui,
+                        right_rect,
+                        &right_response,
+                        &mut compare.uniform_data,
+                        right_key_zoom,
+                        zoom_step,
+                        self.scroll_zoom,
+                        self.live_cycles,
+                    );
+                    paint_fractal(ui, right_rect, &compare.renderer, uniform_data);
+                    if self.show_axes {
+                        draw_axes(ui, right_rect, &compare.uniform_data);
+                    }
+
+                    if compare.sync_navigation {
+                        let (source, target) = if right_active {
+                            (&compare.uniform_data, &mut self.uniform_data)
+                        } else {
+                            (&self.uniform_data, &mut compare.uniform_data)
+                        };
+                        target.center = source.center;
+                        target.zoom = source.zoom;
+                    }
+                }
+            }
         });
     }
 
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, DARK_MODE_KEY, &self.dark_mode);
+        eframe::set_value(storage, PALETTES_KEY, &self.palettes);
+        eframe::set_value(storage, FRAME_RATE_KEY, &self.max_frame_rate);
+        eframe::set_value(storage, SCROLL_ZOOM_KEY, &self.scroll_zoom);
+    }
+
     fn on_exit(&mut self, gl: Option<&glow::Context>) {
         if let Some(gl) = gl {
             self.renderer.lock().destroy(gl);
+            self.julia_inset.lock().destroy(gl);
+            if let Some(compare) = &self.compare {
+                compare.renderer.lock().destroy(gl);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view() -> UniformData {
+        UniformData {
+            resolution: vec2(300., 200.),
+            cycles: 2000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn screenshots_get_all_the_iterations() {
+        let uniform_data = view();
+        let live = live_uniform_data(&uniform_data, Some(MIN_LIVE_CYCLES));
+        assert_eq!(live.cycles, MIN_LIVE_CYCLES);
+        let region = egui::Rect::from_min_max(egui::pos2(0.25, 0.25), egui::pos2(0.75, 0.5));
+        for (region, pixels_per_point) in [(None, None), (Some(region), Some(2.))] {
+            let screenshot = screenshot_uniform_data(&uniform_data, region, pixels_per_point);
+            assert_eq!(screenshot.cycles, uniform_data.cycles);
+            // colored like the live view, which spreads the gradient over all of them too
+            assert_eq!(screenshot.gradient_range(), live.gradient_range());
+        }
+    }
+
+    #[test]
+    fn magnifications_are_grouped_and_rounded() {
+        assert_eq!(format_magnification(1.), "1×");
+        assert_eq!(format_magnification(999.), "999×");
+        assert_eq!(format_magnification(1600000.), "1,600,000×");
+        assert_eq!(format_magnification(1234567.), "1,230,000×");
+        assert_eq!(format_magnification(0.005), "0.005×");
+        assert_eq!(
+            format_magnification(5e24),
+            "5,000,000,000,000,000,000,000,000×"
+        );
+    }
+
+    #[test]
+    fn locked_colors_keep_out_colormaps() {
+        let palette = Palette::from_colormap(Colormap::Viridis);
+        let mut uniform_data = view();
+        apply_palette(&mut uniform_data, &palette, [false; 2]);
+        assert_eq!(uniform_data.colormap, Some(Colormap::Viridis));
+
+        let mut uniform_data = view();
+        let locked = uniform_data.start_color;
+        apply_palette(&mut uniform_data, &palette, [true, false]);
+        assert_eq!(uniform_data.colormap, None);
+        assert_eq!(uniform_data.start_color, locked);
+        assert_eq!(uniform_data.end_color, palette.end_color);
+    }
+
+    #[test]
+    fn locked_colors_keep_out_imported_gradients() {
+        let palette = parse_gimp("GIMP Palette\n0 0 0\n255 0 0\n255 255 0\n", "heat").unwrap();
+        let mut uniform_data = view();
+        apply_palette(&mut uniform_data, &palette, [false; 2]);
+        assert_eq!(uniform_data.gradient_stops, palette.stops);
+
+        let mut uniform_data = view();
+        apply_palette(&mut uniform_data, &palette, [false, true]);
+        assert_eq!(uniform_data.gradient_stops, None);
+    }
+
+    #[test]
+    fn deep_views_keep_their_reference_while_panning() {
+        let mut uniform_data = UniformData {
+            origin: [Fixed::from_f64(-0.75), Fixed::from_f64(0.1)],
+            zoom: 1e8,
+            ..view()
+        };
+        assert!(!needs_rebase(&uniform_data, true));
+        // a drag of a fifth of the view
+        let mut transform = uniform_data.transform();
+        transform.pan(vec2(0.2, 0.));
+        uniform_data.center = transform.center;
+        assert!(!needs_rebase(&uniform_data, false));
+        assert!(needs_rebase(&uniform_data, true));
+        transform.pan(vec2(0.4, 0.));
+        uniform_data.center = transform.center;
+        assert!(needs_rebase(&uniform_data, false));
+    }
+
+    #[test]
+    fn coordinates_have_a_decimal_per_pixel() {
+        let mut uniform_data = UniformData {
+            origin: [Fixed::from_f64(-0.75), Fixed::from_f64(0.1)],
+            ..view()
+        };
+        assert_eq!(
+            format_coordinates(&uniform_data),
+            "re=-0.750 im=0.100 zoom=1.000e0"
+        );
+        uniform_data.zoom_at(1e4, Vec2::ZERO);
+        assert_eq!(
+            format_coordinates(&uniform_data),
+            "re=-0.7500000 im=0.1000000 zoom=1.000e4"
+        );
+    }
+
+    #[test]
+    fn go_to_keeps_every_digit() {
+        let real = "-0.743643887037158704752191506114774";
+        let imaginary = "0.131825904205311970493132056385139";
+        let mut go_to = GoTo {
+            real: real.to_string(),
+            imaginary: imaginary.to_string(),
+            magnification: "1e20".to_string(),
+            animate: false,
+            error: None,
+        };
+        let (point, zoom) = go_to.parse().unwrap();
+        let mut uniform_data = view();
+        uniform_data.zoom = zoom;
+        uniform_data.center_near(point, Vec2::ZERO);
+        let [center_real, center_imaginary] = uniform_data.center_point();
+        assert_eq!(format!("{center_real:.33}"), real);
+        assert_eq!(format!("{center_imaginary:.33}"), imaginary);
+        // the dialog opens on the same point, to a fraction of a pixel
+        let reopened = GoTo::new(&uniform_data).parse().unwrap().0;
+        let pixel = 1. / (uniform_data.zoom * uniform_data.resolution.x) as f64;
+        assert!((reopened[0] - center_real).abs().to_f64() < pixel);
+        assert!((reopened[1] - center_imaginary).abs().to_f64() < pixel);
+
+        for bad in ["1e-5", "inf", "0x10", ""] {
+            go_to.real = bad.to_string();
+            assert!(go_to.parse().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn the_live_view_never_adds_iterations() {
+        let uniform_data = view();
+        assert_eq!(live_uniform_data(&uniform_data, None).cycles, 2000);
+        assert_eq!(live_uniform_data(&uniform_data, Some(5000)).cycles, 2000);
+    }
+}
@@ -0,0 +1,590 @@
+//! Renders a zoom into a saved view frame by frame, without the user interface, for offline
+//! pipelines. The app runs it with `--batch`, see [`BatchOptions`].
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use egui::{vec2, Vec2};
+
+use crate::{
+    app::{Settings, METADATA_KEY},
+    export::{read_metadata, save_image_to, Pixels},
+    gif::{GifEncoder, GifOptions, MAX_COLORS, PALETTE_SAMPLES},
+    renderer::{sanitize_fractal_function, FractalType, Renderer},
+    uniforms::{UniformData, DEEP_ZOOM_START, DEFAULT_ZOOM},
+};
+
+pub const USAGE: &str = "Usage: fractalgui --batch VIEW [--frames N] [--size WIDTHxHEIGHT] \
+                         [--output FOLDER | --output FILE.gif [--colors N] [--no-dither]] \
+                         [--quiet | --verbose] [--stop-on-error]";
+
+/// Time every frame of a GIF is shown, in hundredths of a second, for 25 frames a second.
+const GIF_FRAME_DELAY: u16 = 4;
+
+/// How much the batch prints while it runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only errors.
+    Quiet,
+    /// A status line per frame and a summary at the end.
+    Normal,
+    /// Also the progress within frames and the path of every frame written.
+    Verbose,
+}
+
+/// What `--batch` renders, parsed from the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchOptions {
+    /// Settings file, or PNG exported by the app, with the view the frames zoom into.
+    pub view: PathBuf,
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Folder the frames are written into, as `frame_0001.png` and on, or a `.gif` file
+    /// holding them all.
+    pub output: PathBuf,
+    /// Size of the palette shared by the frames of a GIF.
+    pub colors: usize,
+    /// Dither the frames of a GIF to the palette.
+    pub dither: bool,
+    pub verbosity: Verbosity,
+    /// Stop at the first frame that fails, instead of reporting it and going on.
+    pub stop_on_error: bool,
+}
+
+impl BatchOptions {
+    /// Parses the arguments after the program name, see [`USAGE`].
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let mut options = BatchOptions {
+            view: PathBuf::new(),
+            frames: 60,
+            width: 1280,
+            height: 720,
+            output: PathBuf::from("frames"),
+            colors: MAX_COLORS,
+            dither: true,
+            verbosity: Verbosity::Normal,
+            stop_on_error: false,
+        };
+        let mut view = None;
+        let mut verbosity = None;
+        let mut palette_options = false;
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--batch" => view = Some(PathBuf::from(value()?)),
+                "--frames" => {
+                    options.frames = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&frames| frames > 0)
+                        .ok_or("--frames needs a positive number")?;
+                }
+                "--size" => {
+                    let size = value()?;
+                    [options.width, options.height] = size
+                        .split_once('x')
+                        .and_then(|(width, height)| {
+                            Some([width.parse().ok()?, height.parse().ok()?])
+                        })
+                        .filter(|size: &[u32; 2]| size.iter().all(|&side| side > 0))
+                        .ok_or_else(|| format!("{size:?} isn't a size like 1280x720"))?;
+                }
+                "--output" => options.output = PathBuf::from(value()?),
+                "--colors" => {
+                    options.colors = value()?
+                        .parse()
+                        .ok()
+                        .filter(|colors| (2..=MAX_COLORS).contains(colors))
+                        .ok_or(format!("--colors needs a number from 2 to {MAX_COLORS}"))?;
+                    palette_options = true;
+                }
+                "--no-dither" => {
+                    options.dither = false;
+                    palette_options = true;
+                }
+                "--quiet" | "--verbose" => {
+                    let wanted = if arg == "--quiet" {
+                        Verbosity::Quiet
+                    } else {
+                        Verbosity::Verbose
+                    };
+                    if verbosity.is_some_and(|verbosity| verbosity != wanted) {
+                        return Err("--quiet and --verbose can't be combined".to_string());
+                    }
+                    verbosity = Some(wanted);
+                }
+                "--stop-on-error" => options.stop_on_error = true,
+                _ => return Err(format!("Unknown option {arg}")),
+            }
+        }
+        options.view = view.ok_or("--batch needs the view to render")?;
+        if palette_options && !options.is_gif() {
+            return Err("--colors and --no-dither need a .gif output".to_string());
+        }
+        if options.is_gif() && options.width.max(options.height) > u16::MAX as u32 {
+            return Err(format!("GIFs can't be larger than {0}x{0}", u16::MAX));
+        }
+        options.verbosity = verbosity.unwrap_or(Verbosity::Normal);
+        Ok(options)
+    }
+
+    /// Whether the frames go into one animated GIF instead of a folder of PNGs.
+    fn is_gif(&self) -> bool {
+        self.output
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"))
+    }
+
+    /// Where frame `index`, counting from 0, is written.
+    fn frame_path(&self, index: u32) -> PathBuf {
+        self.output.join(format!("frame_{:04}.png", index + 1))
+    }
+}
+
+/// Reads the view to zoom into, from the metadata of a PNG or from a JSON file.
+fn read_view(path: &Path) -> Result<Settings, String> {
+    let is_png = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+    let json = if is_png {
+        read_metadata(path, METADATA_KEY)?
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {e}", path.display()))?
+    };
+    serde_json::from_str(&json).map_err(|e| format!("Invalid view in {}: {e}", path.display()))
+}
+
+/// The view of frame `index` of `frames`, which zoom from the initial view into `target`
+/// by the same factor every frame, all centered on the middle of `target`.
+fn frame_view(
+    target: &UniformData,
+    fractal_type: FractalType,
+    index: u32,
+    frames: u32,
+    resolution: Vec2,
+) -> UniformData {
+    let t = if frames > 1 {
+        index as f32 / (frames - 1) as f32
+    } else {
+        1.
+    };
+    let zoom = DEFAULT_ZOOM * (target.zoom / DEFAULT_ZOOM).powf(t);
+    // the saved center depends on the aspect ratio, like when the app loads a view
+    let target = UniformData {
+        resolution,
+        ..*target
+    };
+    let mut uniform_data = UniformData {
+        origin: target.center_point(),
+        center: Vec2::ZERO,
+        zoom,
+        window_offset: Vec2::ZERO,
+        convergent: fractal_type == FractalType::Newton,
+        ..target
+    };
+    // like the deep zoom of the app, perturbation is the one that reaches all the way
+    uniform_data.perturbation = fractal_type == FractalType::Mandelbrot
+        && uniform_data.z_seed == Vec2::ZERO
+        && zoom > DEEP_ZOOM_START;
+    if !uniform_data.perturbation {
+        uniform_data.flatten_origin();
+    }
+    uniform_data
+}
+
+/// The status line printed while frame `frame` of `frames`, counting from 1, is rendered,
+/// `percent` of the whole batch being done.
+fn status_line(frame: u32, frames: u32, percent: u32) -> String {
+    format!("frame {frame}/{frames} ({percent}%)")
+}
+
+/// The size frames are rendered at to pick the GIF palette from, small enough that all
+/// `frames` of them together have about [`PALETTE_SAMPLES`] pixels.
+fn palette_sample_size(width: u32, height: u32, frames: u32) -> (u32, u32) {
+    let budget = PALETTE_SAMPLES as f64 / frames as f64;
+    let scale = (budget / (width as f64 * height as f64)).sqrt().min(1.);
+    let side = |length: u32| ((length as f64 * scale).round() as u32).clamp(length.min(8), length);
+    (side(width), side(height))
+}
+
+/// Renders every frame of `options` at [`palette_sample_size`] and returns all their RGBA
+/// pixels, to pick the GIF palette from before the first frame is written. Frames that
+/// fail are left out, rendering them for real reports the error.
+fn palette_samples(
+    gl: &glow::Context,
+    renderer: &mut Renderer,
+    settings: &Settings,
+    options: &BatchOptions,
+) -> Vec<u8> {
+    let (width, height) = palette_sample_size(options.width, options.height, options.frames);
+    let mut samples = Vec::new();
+    for index in 0..options.frames {
+        let uniform_data = frame_view(
+            &settings.uniform_data,
+            settings.fractal_type,
+            index,
+            options.frames,
+            vec2(width as f32, height as f32),
+        );
+        if uniform_data.perturbation {
+            renderer.update_reference_orbit(gl, &uniform_data);
+        }
+        if let Ok(pixels) = renderer.render_to_buffer(gl, width, height, uniform_data, &mut |_| {})
+        {
+            samples.extend(pixels);
+        }
+    }
+    samples
+}
+
+/// Renders and writes every frame of `options`, reporting frames that fail and going on
+/// unless [`BatchOptions::stop_on_error`] is set. Returns whether all of them were written,
+/// or an error when nothing can be rendered or the GIF can't be written. `gl` needs a
+/// current OpenGL 3.3 context.
+pub fn run_batch(gl: &glow::Context, options: &BatchOptions) -> Result<bool, String> {
+    let settings = read_view(&options.view)?;
+    let function = sanitize_fractal_function(&settings.custom_fractal_function)?;
+    let folder = if options.is_gif() {
+        options.output.parent().unwrap_or(Path::new(""))
+    } else {
+        &options.output
+    };
+    std::fs::create_dir_all(folder)
+        .map_err(|e| format!("Cannot create {}: {e}", folder.display()))?;
+    let mut renderer = Renderer::new(gl)?;
+    if let Err(error) = renderer.set_fractal_type(gl, settings.fractal_type, &function) {
+        renderer.destroy(gl);
+        return Err(error.to_string());
+    }
+
+    let start = Instant::now();
+    let frames = options.frames;
+    let (width, height) = (options.width, options.height);
+    // the GIF gets every frame as soon as it's rendered, so only one is kept in memory
+    let mut gif = None;
+    if options.is_gif() {
+        let samples = palette_samples(gl, &mut renderer, &settings, options);
+        if options.verbosity == Verbosity::Verbose {
+            println!(
+                "picked the palette in {:.2} s",
+                start.elapsed().as_secs_f64()
+            );
+        }
+        let gif_options = GifOptions {
+            colors: options.colors,
+            dither: options.dither,
+            delay: GIF_FRAME_DELAY,
+        };
+        let encoder = File::create(&options.output)
+            .map_err(|e| format!("Cannot write {}: {e}", options.output.display()))
+            .and_then(|file| {
+                GifEncoder::new(BufWriter::new(file), width, height, &samples, gif_options)
+            });
+        match encoder {
+            Ok(encoder) => gif = Some(encoder),
+            Err(error) => {
+                renderer.destroy(gl);
+                return Err(error);
+            }
+        }
+    }
+    // frames rendered, and the files holding them
+    let mut done = 0;
+    let mut written = Vec::new();
+    for index in 0..frames {
+        let frame_start = Instant::now();
+        let uniform_data = frame_view(
+            &settings.uniform_data,
+            settings.fractal_type,
+            index,
+            frames,
+            vec2(width as f32, height as f32),
+        );
+        if uniform_data.perturbation {
+            renderer.update_reference_orbit(gl, &uniform_data);
+        }
+        let mut shown = index * 100 / frames;
+        let mut progress = |fraction: f32| {
+            let percent = ((index as f32 + fraction) / frames as f32 * 100.) as u32;
+            if options.verbosity == Verbosity::Verbose && percent > shown {
+                println!("{}", status_line(index + 1, frames, percent));
+                shown = percent;
+            }
+        };
+        let path = options.frame_path(index);
+        let metadata = serde_json::to_string(&Settings {
+            uniform_data,
+            ..settings.clone()
+        })
+        .expect("Settings should always serialize");
+        let result = renderer
+            .render_to_buffer(gl, width, height, uniform_data, &mut progress)
+            .and_then(|pixels| match &mut gif {
+                Some(gif) => gif.add_frame(&pixels),
+                None => {
+                    let pixels = Pixels::Rgba8(pixels);
+                    save_image_to(&path, width, height, &pixels, (METADATA_KEY, &metadata))
+                }
+            });
+        match result {
+            Ok(()) => {
+                if options.verbosity == Verbosity::Verbose {
+                    let seconds = frame_start.elapsed().as_secs_f64();
+                    if options.is_gif() {
+                        println!("wrote frame {} in {seconds:.2} s", index + 1);
+                    } else {
+                        println!("wrote {} in {seconds:.2} s", path.display());
+                    }
+                } else if options.verbosity == Verbosity::Normal {
+                    println!(
+                        "{}",
+                        status_line(index + 1, frames, (index + 1) * 100 / frames)
+                    );
+                }
+                done += 1;
+                if !options.is_gif() {
+                    written.push(path);
+                }
+            }
+            Err(error) => {
+                eprintln!("frame {}/{frames} failed: {error}", index + 1);
+                if options.stop_on_error {
+                    break;
+                }
+            }
+        }
+    }
+    renderer.destroy(gl);
+
+    if let Some(gif) = gif {
+        gif.finish()?;
+        if done > 0 {
+            written.push(options.output.clone());
+        }
+    }
+    if options.verbosity != Verbosity::Quiet {
+        println!(
+            "Wrote {done} of {frames} frames in {:.1} s",
+            start.elapsed().as_secs_f64()
+        );
+        match (written.first(), written.last()) {
+            (Some(first), Some(last)) if first != last => {
+                println!("{} … {}", first.display(), last.display());
+            }
+            (Some(path), _) => println!("{}", path.display()),
+            _ => {}
+        }
+    }
+    Ok(done == frames)
+}
+
+/// Runs [`run_batch`], exiting with an error when any frame failed.
+pub fn run_batch_or_exit(gl: &glow::Context, options: &BatchOptions) {
+    match run_batch(gl, options) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(error) => {
+            eprintln!("The batch failed: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// An OpenGL context of the first EGL device, without a window or a display, so batches run
+/// on servers and in CI.
+#[cfg(not(target_os = "macos"))]
+pub struct HeadlessContext {
+    gl: glow::Context,
+    // `gl` only holds functions of the context, which has to outlive it
+    _context: glutin::api::egl::context::PossiblyCurrentContext,
+}
+
+#[cfg(not(target_os = "macos"))]
+impl HeadlessContext {
+    /// Creates an OpenGL 3.3 context and makes it current, without a surface to draw on,
+    /// which the renderer doesn't need.
+    pub fn new() -> Result<Self, String> {
+        use glutin::{
+            api::egl::{device::Device, display::Display},
+            config::{ConfigSurfaceTypes, ConfigTemplateBuilder},
+            context::{ContextApi, ContextAttributesBuilder, GlProfile, Version},
+            prelude::*,
+        };
+
+        let device = Device::query_devices()
+            .map_err(|e| format!("Cannot list EGL devices: {e}"))?
+            .next()
+            .ok_or("There's no EGL device")?;
+        let display = unsafe { Display::with_device(&device, None) }
+            .map_err(|e| format!("Cannot open the EGL device: {e}"))?;
+        let template = ConfigTemplateBuilder::new()
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .build();
+        let config = unsafe { display.find_configs(template) }
+            .map_err(|e| format!("Cannot find an EGL config: {e}"))?
+            .next()
+            .ok_or("There's no EGL config without a surface")?;
+        let attributes = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+            .with_profile(GlProfile::Core)
+            .build(None);
+        let context = unsafe { display.create_context(&config, &attributes) }
+            .and_then(|context| context.make_current_surfaceless())
+            .map_err(|e| format!("Cannot create an OpenGL 3.3 context: {e}"))?;
+        let gl = unsafe {
+            glow::Context::from_loader_function_cstr(|name| display.get_proc_address(name))
+        };
+        Ok(Self {
+            gl,
+            _context: context,
+        })
+    }
+
+    pub fn gl(&self) -> &glow::Context {
+        &self.gl
+    }
+}
+
+/// Runs [`run_batch`] on the first frame and closes the window, exiting with an error when
+/// any frame failed. The GL context comes from a window, which eframe keeps hidden until
+/// after the first frame, for where there's no [`HeadlessContext`].
+pub struct Batch {
+    options: BatchOptions,
+    done: bool,
+}
+
+impl Batch {
+    pub fn new(options: BatchOptions) -> Self {
+        Self {
+            options,
+            done: false,
+        }
+    }
+}
+
+impl eframe::App for Batch {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let Some(gl) = frame.gl() else {
+            return;
+        };
+        if self.done {
+            return;
+        }
+        run_batch_or_exit(gl, &self.options);
+        self.done = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<BatchOptions, String> {
+        BatchOptions::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn options_have_defaults() {
+        let options = parse("--batch view.png").unwrap();
+        assert_eq!(options.view, PathBuf::from("view.png"));
+        assert_eq!(
+            (options.frames, options.width, options.height),
+            (60, 1280, 720)
+        );
+        assert_eq!(options.verbosity, Verbosity::Normal);
+        assert!(!options.stop_on_error);
+    }
+
+    #[test]
+    fn options_are_read() {
+        let options = parse(
+            "--batch a.json --frames 120 --size 640x480 --output out --quiet --stop-on-error",
+        )
+        .unwrap();
+        assert_eq!(
+            (options.frames, options.width, options.height),
+            (120, 640, 480)
+        );
+        assert_eq!(options.frame_path(11), PathBuf::from("out/frame_0012.png"));
+        assert_eq!(options.verbosity, Verbosity::Quiet);
+        assert!(options.stop_on_error);
+        assert!(!options.is_gif());
+
+        let options = parse("--batch a.json --output zoom.GIF --colors 64 --no-dither").unwrap();
+        assert!(options.is_gif());
+        assert_eq!((options.colors, options.dither), (64, false));
+        assert!(parse("--batch a.json --output zoom.gif").unwrap().dither);
+    }
+
+    #[test]
+    fn palette_samples_are_small() {
+        let (width, height) = palette_sample_size(1280, 720, 60);
+        assert!((width as f32 / height as f32 - 16. / 9.).abs() < 0.1);
+        assert!((width * height * 60) as usize <= PALETTE_SAMPLES * 11 / 10);
+        assert_eq!(palette_sample_size(16, 8, 2), (16, 8));
+        assert_eq!(palette_sample_size(4000, 4000, 100_000), (8, 8));
+        assert_eq!(palette_sample_size(4000, 2, 100_000), (36, 2));
+    }
+
+    #[test]
+    fn bad_options_are_rejected() {
+        for args in [
+            "",
+            "--batch",
+            "--batch a.json --frames 0",
+            "--batch a.json --size 640",
+            "--batch a.json --size 0x480",
+            "--batch a.json --quiet --verbose",
+            "--batch a.json --fast",
+            "--batch a.json --colors 16",
+            "--batch a.json --output a.gif --colors 300",
+            "--batch a.json --output a.gif --size 70000x10",
+        ] {
+            assert!(parse(args).is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn status_lines_count_from_one() {
+        assert_eq!(status_line(12, 120, 10), "frame 12/120 (10%)");
+    }
+
+    #[test]
+    fn frames_zoom_evenly_into_the_view() {
+        let mut target = UniformData {
+            resolution: vec2(200., 100.),
+            ..Default::default()
+        };
+        target.zoom_at(1e3, Vec2::ZERO);
+        target.center_on(vec2(-0.75, 0.1));
+        let resolution = vec2(200., 100.);
+        let view = |index| frame_view(&target, FractalType::Phoenix, index, 5, resolution);
+        assert_eq!(view(0).zoom, DEFAULT_ZOOM);
+        assert!((view(4).zoom / target.zoom - 1.).abs() < 1e-5);
+        let ratio = |index: u32| view(index + 1).zoom / view(index).zoom;
+        assert!((ratio(0) - ratio(3)).abs() < 1e-3);
+        for index in 0..5 {
+            let middle = view(index).plane_point(vec2(0.5, 0.5));
+            assert!((middle - vec2(-0.75, 0.1)).length() < 1e-5, "{middle:?}");
+        }
+    }
+
+    #[test]
+    fn deep_frames_are_perturbed() {
+        let target = UniformData {
+            zoom: 1e20,
+            ..Default::default()
+        };
+        let view = |index| frame_view(&target, FractalType::Mandelbrot, index, 3, vec2(1., 1.));
+        assert!(!view(0).perturbation);
+        assert!(view(2).perturbation);
+        assert_eq!(view(2).origin, target.center_point());
+    }
+}
@@ -0,0 +1,120 @@
+//! Measures how fast the shader renders, for comparing GPUs and catching regressions. The app
+//! runs it with `--bench`, see [`Benchmark`].
+
+use std::time::{Duration, Instant};
+
+use egui::vec2;
+
+use crate::{renderer::Renderer, uniforms::UniformData};
+
+/// Widths and heights of the square images rendered.
+const SIZES: [u32; 3] = [512, 1024, 2048];
+
+/// Iteration counts rendered at every size.
+const CYCLES: [i32; 3] = [100, 1000, 5000];
+
+/// How long rendering one image took.
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+    pub size: u32,
+    pub cycles: i32,
+    pub time: Duration,
+}
+
+impl Measurement {
+    pub fn megapixels_per_second(&self) -> f64 {
+        (self.size as f64 * self.size as f64) / 1e6 / self.time.as_secs_f64()
+    }
+}
+
+/// The view every measurement renders, the Seahorse valley with interior, exterior and a
+/// long boundary, so neither the escaping nor the bounded orbits dominate.
+fn reference_view(size: u32, cycles: i32) -> UniformData {
+    let mut uniform_data = UniformData {
+        resolution: vec2(size as f32, size as f32),
+        zoom: 2.,
+        cycles,
+        ..Default::default()
+    };
+    uniform_data.center_on(vec2(-0.75, 0.1));
+    uniform_data
+}
+
+/// Renders the reference view of the Mandelbrot set at every size and iteration count with
+/// [`Renderer::render_to_buffer`], which waits for the GPU, so the times include reading
+/// the image back. `gl` needs a current OpenGL 3.3 context.
+pub fn run_benchmark(gl: &glow::Context) -> Result<Vec<Measurement>, String> {
+    let renderer = Renderer::new(gl)?;
+    // the first draw pays for the driver finishing the program
+    let warm_up = renderer.render_to_buffer(gl, 64, 64, reference_view(64, 100), &mut |_| {});
+    let mut measurements = Vec::new();
+    let result = warm_up.and_then(|_| {
+        for size in SIZES {
+            for cycles in CYCLES {
+                let start = Instant::now();
+                renderer.render_to_buffer(
+                    gl,
+                    size,
+                    size,
+                    reference_view(size, cycles),
+                    &mut |_| {},
+                )?;
+                measurements.push(Measurement {
+                    size,
+                    cycles,
+                    time: start.elapsed(),
+                });
+            }
+        }
+        Ok(())
+    });
+    renderer.destroy(gl);
+    result.map(|()| measurements)
+}
+
+/// Formats `measurements` as CSV with a header line, followed by a comment with the total.
+pub fn to_csv(measurements: &[Measurement]) -> String {
+    let mut csv = "width,height,cycles,seconds,megapixels_per_second\n".to_string();
+    for m in measurements {
+        csv += &format!(
+            "{},{},{},{:.6},{:.3}\n",
+            m.size,
+            m.size,
+            m.cycles,
+            m.time.as_secs_f64(),
+            m.megapixels_per_second()
+        );
+    }
+    let total: Duration = measurements.iter().map(|m| m.time).sum();
+    csv += &format!("# total seconds: {:.6}\n", total.as_secs_f64());
+    csv
+}
+
+/// Runs [`run_benchmark`] on the first frame, prints the CSV to stdout and closes the window,
+/// or exits with an error. The GL context comes from a window, since that's what eframe makes.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+pub struct Benchmark {
+    done: bool,
+}
+
+#[cfg(feature = "gui")]
+impl eframe::App for Benchmark {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let Some(gl) = frame.gl() else {
+            return;
+        };
+        if self.done {
+            return;
+        }
+        match run_benchmark(gl) {
+            Ok(measurements) => print!("{}", to_csv(&measurements)),
+            Err(error) => {
+                eprintln!("The benchmark failed: {error}");
+                std::process::exit(1);
+            }
+        }
+        self.done = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+}
@@ -0,0 +1,46 @@
+// Arithmetic on pairs of floats, `x` holding the rounded value and `y` the rounding error,
+// which together carry about twice the precision of a float. Only exact if the driver
+// rounds every operation and doesn't reorder them, see `Renderer::double_float_supported`.
+
+// always 1, multiplying sums by it keeps compilers from simplifying `(a + b) - a` to `b`
+uniform float df_one;
+
+// a + b as a pair, exactly
+vec2 two_sum(float a, float b) {
+    float s = (a + b) * df_one;
+    float v = s - a;
+    return vec2(s, (a - (s - v)) + (b - v));
+}
+
+// like `two_sum`, but only exact when |a| >= |b|
+vec2 quick_two_sum(float a, float b) {
+    float s = (a + b) * df_one;
+    return vec2(s, b - (s - a));
+}
+
+// splits `a` into two halves of 12 bits, whose products don't need rounding
+vec2 split(float a) {
+    float t = 4097. * a;
+    float hi = t - (t - a);
+    return vec2(hi, a - hi);
+}
+
+// a * b as a pair, exactly
+vec2 two_prod(float a, float b) {
+    float p = a * b;
+    vec2 a_split = split(a);
+    vec2 b_split = split(b);
+    float error = ((a_split.x * b_split.x - p) + a_split.x * b_split.y + a_split.y * b_split.x)
+        + a_split.y * b_split.y;
+    return vec2(p, error);
+}
+
+vec2 df_add(vec2 a, vec2 b) {
+    vec2 s = two_sum(a.x, b.x);
+    return quick_two_sum(s.x, s.y + a.y + b.y);
+}
+
+vec2 df_mul(vec2 a, vec2 b) {
+    vec2 p = two_prod(a.x, b.x);
+    return quick_two_sum(p.x, p.y + a.x * b.y + a.y * b.x);
+}
@@ -0,0 +1,541 @@
+use std::path::{Path, PathBuf};
+
+/// Precision of the buffer rendered for export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportDepth {
+    Eight,
+    Sixteen,
+    Float,
+    /// Raw iteration counts instead of colors.
+    Iterations,
+    /// Which pixels are in the set, for compositing.
+    SetMask,
+}
+
+impl ExportDepth {
+    pub const ALL: [ExportDepth; 5] = [
+        ExportDepth::Eight,
+        ExportDepth::Sixteen,
+        ExportDepth::Float,
+        ExportDepth::Iterations,
+        ExportDepth::SetMask,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportDepth::Eight => "8-bit",
+            ExportDepth::Sixteen => "16-bit",
+            ExportDepth::Float => "HDR",
+            ExportDepth::Iterations => "Iterations",
+            ExportDepth::SetMask => "Mask",
+        }
+    }
+}
+
+/// RGBA pixels as read back from the renderer.
+pub enum Pixels {
+    Rgba8(Vec<u8>),
+    Rgba16(Vec<u16>),
+    /// Unclamped color straight from the shader, in the same (sRGB) encoding as the other variants.
+    RgbaF32(Vec<f32>),
+    /// One iteration count per pixel, saved as grayscale.
+    Iterations(Vec<u16>),
+    /// Coverage of the set per pixel, saved as white with that alpha.
+    Mask(Vec<u8>),
+}
+
+impl Pixels {
+    /// Number of pixels, regardless of how many values each has.
+    pub fn pixel_count(&self) -> usize {
+        match self {
+            Pixels::Rgba8(pixels) => pixels.len() / 4,
+            Pixels::Rgba16(pixels) => pixels.len() / 4,
+            Pixels::RgbaF32(pixels) => pixels.len() / 4,
+            Pixels::Iterations(counts) => counts.len(),
+            Pixels::Mask(coverage) => coverage.len(),
+        }
+    }
+
+    /// Adds the rows of `other` below these, both have to be of the same kind and width.
+    pub fn append(&mut self, other: Pixels) {
+        match (self, other) {
+            (Pixels::Rgba8(pixels), Pixels::Rgba8(other)) => pixels.extend(other),
+            (Pixels::Rgba16(pixels), Pixels::Rgba16(other)) => pixels.extend(other),
+            (Pixels::RgbaF32(pixels), Pixels::RgbaF32(other)) => pixels.extend(other),
+            (Pixels::Iterations(counts), Pixels::Iterations(other)) => counts.extend(other),
+            (Pixels::Mask(coverage), Pixels::Mask(other)) => coverage.extend(other),
+            _ => panic!("Only pixels of the same kind can be joined"),
+        }
+    }
+
+    /// Converts to 16 bits per channel, clamping floating point values.
+    fn to_rgba16(&self) -> Vec<u16> {
+        match self {
+            Pixels::Rgba8(pixels) => pixels.iter().map(|&c| c as u16 * 257).collect(),
+            Pixels::Rgba16(pixels) => pixels.clone(),
+            Pixels::RgbaF32(pixels) => pixels
+                .iter()
+                .map(|&c| (c.clamp(0., 1.) * u16::MAX as f32).round() as u16)
+                .collect(),
+            Pixels::Iterations(counts) => counts
+                .iter()
+                .flat_map(|&count| [count, count, count, u16::MAX])
+                .collect(),
+            Pixels::Mask(coverage) => coverage
+                .iter()
+                .flat_map(|&c| [u16::MAX, u16::MAX, u16::MAX, c as u16 * 257])
+                .collect(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn to_rgba_f32(&self) -> Vec<f32> {
+        match self {
+            Pixels::Rgba8(pixels) => pixels.iter().map(|&c| c as f32 / 255.).collect(),
+            Pixels::Rgba16(pixels) => pixels.iter().map(|&c| c as f32 / 65535.).collect(),
+            Pixels::RgbaF32(pixels) => pixels.clone(),
+            Pixels::Iterations(_) | Pixels::Mask(_) => self
+                .to_rgba16()
+                .iter()
+                .map(|&c| c as f32 / 65535.)
+                .collect(),
+        }
+    }
+
+    /// Scales pixels rendered at `factor` times `width` by `height` down to that size with
+    /// `filter`.
+    pub fn downsample(self, width: u32, height: u32, factor: u32, filter: Downsampling) -> Pixels {
+        if factor == 1 {
+            return self;
+        }
+        let (width, height, factor) = (width as usize, height as usize, factor as usize);
+        let horizontal = filter.weights(width, factor);
+        let vertical = filter.weights(height, factor);
+        let resample = |pixels: &[f32], channels| {
+            resample(pixels, width * factor, channels, &horizontal, &vertical)
+        };
+        // integer channels saturate at their limits when filters overshoot
+        match self {
+            Pixels::Rgba8(pixels) => {
+                let pixels: Vec<f32> = pixels.iter().map(|&c| c as f32).collect();
+                Pixels::Rgba8(
+                    resample(&pixels, 4)
+                        .iter()
+                        .map(|&c| c.round() as u8)
+                        .collect(),
+                )
+            }
+            Pixels::Rgba16(pixels) => {
+                let pixels: Vec<f32> = pixels.iter().map(|&c| c as f32).collect();
+                Pixels::Rgba16(
+                    resample(&pixels, 4)
+                        .iter()
+                        .map(|&c| c.round() as u16)
+                        .collect(),
+                )
+            }
+            Pixels::RgbaF32(pixels) => Pixels::RgbaF32(resample(&pixels, 4)),
+            Pixels::Iterations(counts) => {
+                let counts: Vec<f32> = counts.iter().map(|&c| c as f32).collect();
+                Pixels::Iterations(
+                    resample(&counts, 1)
+                        .iter()
+                        .map(|&c| c.round() as u16)
+                        .collect(),
+                )
+            }
+            Pixels::Mask(coverage) => {
+                let coverage: Vec<f32> = coverage.iter().map(|&c| c as f32).collect();
+                Pixels::Mask(
+                    resample(&coverage, 1)
+                        .iter()
+                        .map(|&c| c.round() as u8)
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+/// How supersampled exports are scaled down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Downsampling {
+    /// Averages all samples of a pixel, smooth without ringing.
+    #[default]
+    Box,
+    /// Lanczos-3, sharper than the box filter but with slight halos at hard edges.
+    Lanczos,
+    /// Keeps the sample in the middle of each pixel, as sharp and aliased as no supersampling.
+    Nearest,
+}
+
+impl Downsampling {
+    pub const ALL: [Downsampling; 3] = [
+        Downsampling::Box,
+        Downsampling::Lanczos,
+        Downsampling::Nearest,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Downsampling::Box => "Box",
+            Downsampling::Lanczos => "Lanczos",
+            Downsampling::Nearest => "Nearest",
+        }
+    }
+
+    /// For each of `len` output pixels along one axis, the source pixels and their weights,
+    /// when the source is `factor` times longer.
+    fn weights(self, len: usize, factor: usize) -> Vec<Vec<(usize, f32)>> {
+        (0..len)
+            .map(|i| match self {
+                Downsampling::Box => (i * factor..(i + 1) * factor)
+                    .map(|source| (source, 1. / factor as f32))
+                    .collect(),
+                Downsampling::Nearest => vec![(i * factor + factor / 2, 1.)],
+                Downsampling::Lanczos => {
+                    const LOBES: f32 = 3.;
+                    let center = (i as f32 + 0.5) * factor as f32;
+                    let radius = LOBES * factor as f32;
+                    let first = (center - radius).floor() as isize;
+                    let last = (center + radius).ceil() as isize;
+                    let mut weights: Vec<(usize, f32)> = (first..last)
+                        .map(|source| {
+                            let x = (source as f32 + 0.5 - center) / factor as f32;
+                            // repeat the edge pixels beyond the image
+                            let clamped = source.clamp(0, (len * factor) as isize - 1);
+                            (clamped as usize, lanczos(x, LOBES))
+                        })
+                        .collect();
+                    let total: f32 = weights.iter().map(|(_, weight)| weight).sum();
+                    for (_, weight) in &mut weights {
+                        *weight /= total;
+                    }
+                    weights
+                }
+            })
+            .collect()
+    }
+}
+
+fn lanczos(x: f32, lobes: f32) -> f32 {
+    if x == 0. {
+        return 1.;
+    }
+    if x.abs() >= lobes {
+        return 0.;
+    }
+    let pi_x = std::f32::consts::PI * x;
+    lobes * pi_x.sin() * (pi_x / lobes).sin() / (pi_x * pi_x)
+}
+
+/// Scales an image with `channels` values per pixel that's `source_width` wide, first along
+/// the rows and then along the columns, with weights from [`Downsampling::weights`].
+fn resample(
+    pixels: &[f32],
+    source_width: usize,
+    channels: usize,
+    horizontal: &[Vec<(usize, f32)>],
+    vertical: &[Vec<(usize, f32)>],
+) -> Vec<f32> {
+    let width = horizontal.len();
+    let source_height = pixels.len() / (source_width * channels);
+    let mut rows: Vec<f32> = Vec::with_capacity(width * source_height * channels);
+    for y in 0..source_height {
+        for weights in horizontal {
+            for channel in 0..channels {
+                rows.push(
+                    weights
+                        .iter()
+                        .map(|&(x, weight)| {
+                            pixels[(y * source_width + x) * channels + channel] * weight
+                        })
+                        .sum(),
+                );
+            }
+        }
+    }
+    let mut output = Vec::with_capacity(width * vertical.len() * channels);
+    for weights in vertical {
+        for x in 0..width {
+            for channel in 0..channels {
+                output.push(
+                    weights
+                        .iter()
+                        .map(|&(y, weight)| rows[(y * width + x) * channels + channel] * weight)
+                        .sum(),
+                );
+            }
+        }
+    }
+    output
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes `pixels` as a binary PPM (alpha is dropped).
+fn encode_ppm(width: u32, height: u32, pixels: &Pixels) -> Vec<u8> {
+    match pixels {
+        Pixels::RgbaF32(_) | Pixels::Iterations(_) | Pixels::Mask(_) => {
+            encode_ppm(width, height, &Pixels::Rgba16(pixels.to_rgba16()))
+        }
+        Pixels::Rgba8(pixels) => {
+            let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+            for rgba in pixels.chunks_exact(4) {
+                data.extend_from_slice(&rgba[..3]);
+            }
+            data
+        }
+        Pixels::Rgba16(pixels) => {
+            let mut data = format!("P6\n{} {}\n65535\n", width, height).into_bytes();
+            for rgba in pixels.chunks_exact(4) {
+                for channel in &rgba[..3] {
+                    data.extend_from_slice(&channel.to_be_bytes());
+                }
+            }
+            data
+        }
+    }
+}
+
+/// Text attached to an image as `(key, value)`, only PNG can hold it.
+pub type Metadata<'a> = (&'a str, &'a str);
+
+fn encode_png(
+    width: u32,
+    height: u32,
+    pixels: &Pixels,
+    metadata: Metadata,
+) -> Result<Vec<u8>, String> {
+    if let Pixels::RgbaF32(_) = pixels {
+        let pixels = Pixels::Rgba16(pixels.to_rgba16());
+        return encode_png(width, height, &pixels, metadata);
+    }
+
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(match pixels {
+        Pixels::Iterations(_) => png::ColorType::Grayscale,
+        Pixels::Mask(_) => png::ColorType::GrayscaleAlpha,
+        _ => png::ColorType::Rgba,
+    });
+    let (key, value) = metadata;
+    encoder
+        .add_itxt_chunk(key.to_string(), value.to_string())
+        .map_err(|e| format!("Cannot add metadata: {e}"))?;
+    let result = match pixels {
+        Pixels::Rgba8(pixels) => {
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(pixels))
+        }
+        Pixels::Rgba16(pixels) | Pixels::Iterations(pixels) => {
+            encoder.set_depth(png::BitDepth::Sixteen);
+            let bytes: Vec<u8> = pixels.iter().flat_map(|c| c.to_be_bytes()).collect();
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&bytes))
+        }
+        Pixels::Mask(coverage) => {
+            encoder.set_depth(png::BitDepth::Eight);
+            let bytes: Vec<u8> = coverage.iter().flat_map(|&c| [u8::MAX, c]).collect();
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&bytes))
+        }
+        Pixels::RgbaF32(_) => unreachable!("converted to 16 bits above"),
+    };
+    result.map_err(|e| format!("Cannot encode PNG: {e}"))?;
+    Ok(data)
+}
+
+/// Encodes `pixels` as an OpenEXR image with linear color, as the format expects.
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_exr(width: u32, height: u32, pixels: &Pixels) -> Result<Vec<u8>, String> {
+    use exr::prelude::*;
+
+    let pixels = pixels.to_rgba_f32();
+    let channels = SpecificChannels::rgba(|Vec2(x, y): Vec2<usize>| {
+        let i = (y * width as usize + x) * 4;
+        (
+            srgb_to_linear(pixels[i]),
+            srgb_to_linear(pixels[i + 1]),
+            srgb_to_linear(pixels[i + 2]),
+            pixels[i + 3],
+        )
+    });
+    let image = Image::from_channels((width as usize, height as usize), channels);
+
+    let mut data = std::io::Cursor::new(Vec::new());
+    image
+        .write()
+        .to_buffered(&mut data)
+        .map_err(|e| format!("Cannot encode EXR: {e}"))?;
+    Ok(data.into_inner())
+}
+
+/// Encodes iteration counts as a NumPy `.npy` array of shape `(height, width)`.
+fn encode_npy(width: u32, height: u32, pixels: &Pixels) -> Result<Vec<u8>, String> {
+    let Pixels::Iterations(counts) = pixels else {
+        return Err("Only iteration counts can be saved as .npy".to_string());
+    };
+
+    let header =
+        format!("{{'descr': '<u2', 'fortran_order': False, 'shape': ({height}, {width}), }}");
+    // magic, version and header length take 10 bytes, the whole header is padded to 64
+    let length = (10 + header.len() + 1).next_multiple_of(64) - 10;
+    let header = format!("{header:<0$}\n", length - 1);
+
+    let mut data = b"\x93NUMPY\x01\x00".to_vec();
+    data.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    data.extend_from_slice(header.as_bytes());
+    data.extend(counts.iter().flat_map(|count| count.to_le_bytes()));
+    Ok(data)
+}
+
+/// Encodes the image in the format given by the extension of `path`.
+fn encode_for_path(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &Pixels,
+    metadata: Metadata,
+) -> Result<Vec<u8>, String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("ppm") => Ok(encode_ppm(width, height, pixels)),
+        Some("png") => encode_png(width, height, pixels, metadata),
+        Some("npy") => encode_npy(width, height, pixels),
+        #[cfg(not(target_arch = "wasm32"))]
+        Some("exr") => encode_exr(width, height, pixels),
+        Some(other) => Err(format!("Unsupported image format: .{other}")),
+        None => Err("The file name needs an extension, e.g. .png".to_string()),
+    }
+}
+
+/// Asks the user where to save the image, starting in `directory`, which is
+/// updated to the chosen folder.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_image(
+    directory: &mut Option<PathBuf>,
+    width: u32,
+    height: u32,
+    pixels: &Pixels,
+    metadata: Metadata,
+) -> Result<String, String> {
+    let mut dialog = rfd::FileDialog::new()
+        .set_file_name("output.png")
+        .add_filter("PNG image", &["png"])
+        .add_filter("PPM image", &["ppm"])
+        .add_filter("OpenEXR image", &["exr"])
+        .add_filter("NumPy array", &["npy"]);
+    if let Some(directory) = directory {
+        dialog = dialog.set_directory(directory);
+    }
+    let Some(path) = dialog.save_file() else {
+        return Ok("Screenshot cancelled".to_string());
+    };
+    *directory = path.parent().map(Path::to_path_buf);
+
+    let data = encode_for_path(&path, width, height, pixels, metadata)?;
+    std::fs::write(&path, data).map_err(|e| format!("Cannot write {}: {e}", path.display()))?;
+    Ok(format!("Saved {}", path.display()))
+}
+
+/// Saves the image as `fractal_0001.<extension>` in `directory`, or with the next number
+/// that isn't taken yet, without asking.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_image_numbered(
+    directory: &Path,
+    extension: &str,
+    width: u32,
+    height: u32,
+    pixels: &Pixels,
+    metadata: Metadata,
+) -> Result<String, String> {
+    let path = (1..)
+        .map(|index| directory.join(format!("fractal_{index:04}.{extension}")))
+        .find(|path| !path.exists())
+        .expect("Some file name should be free");
+    save_image_to(&path, width, height, pixels, metadata)?;
+    Ok(format!("Saved {}", path.display()))
+}
+
+/// Saves the image as `path`, in the format given by its extension.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_image_to(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &Pixels,
+    metadata: Metadata,
+) -> Result<(), String> {
+    let data = encode_for_path(path, width, height, pixels, metadata)?;
+    std::fs::write(path, data).map_err(|e| format!("Cannot write {}: {e}", path.display()))
+}
+
+/// Offers the image to the user as a download of `output.png`.
+#[cfg(target_arch = "wasm32")]
+pub fn save_image(
+    _directory: &mut Option<PathBuf>,
+    width: u32,
+    height: u32,
+    pixels: &Pixels,
+    metadata: Metadata,
+) -> Result<String, String> {
+    use eframe::wasm_bindgen::JsCast as _;
+
+    let data = encode_for_path(Path::new("output.png"), width, height, pixels, metadata)?;
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data.as_slice()));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)
+        .map_err(|_| "Cannot create image data in the browser".to_string())?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)
+        .map_err(|_| "Cannot create a download link for the image".to_string())?;
+
+    let anchor = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.create_element("a").ok())
+        .and_then(|a| a.dyn_into::<web_sys::HtmlAnchorElement>().ok());
+    let Some(anchor) = anchor else {
+        return Err("The browser blocked the download, check your popup settings".to_string());
+    };
+    anchor.set_href(&url);
+    anchor.set_download("output.png");
+    anchor.click();
+    Ok("Download started".to_string())
+}
+
+/// Reads the text stored under `key` in a PNG file.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_metadata(path: &Path, key: &str) -> Result<String, String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+    let reader = png::Decoder::new(file)
+        .read_info()
+        .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+    let info = reader.info();
+
+    let utf8 = info.utf8_text.iter().find(|chunk| chunk.keyword == key);
+    let latin1 = info
+        .uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == key);
+    match (utf8, latin1) {
+        (Some(chunk), _) => chunk
+            .get_text()
+            .map_err(|e| format!("Cannot read metadata: {e}")),
+        (None, Some(chunk)) => Ok(chunk.text.clone()),
+        (None, None) => Err(format!("{} doesn't contain a saved view", path.display())),
+    }
+}
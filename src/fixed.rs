@@ -0,0 +1,345 @@
+use std::{
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Number of 32-bit limbs of [`Fixed`], the last one holds the integer part.
+const LIMBS: usize = 6;
+
+/// Decimals [`Fixed`] is printed with when no precision is given, enough to parse back to
+/// the same number.
+const DECIMALS: usize = 49;
+
+/// Signed fixed point number with 32 bits before the point and 160 after it, which is about
+/// 48 decimals. Keeps the reference orbit and the point it starts at precise in views far
+/// deeper than double precision reaches.
+///
+/// Stored in two's complement, least significant limb first. Overflowing the integer part
+/// wraps around, the Mandelbrot orbit is stopped long before that.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Fixed([u32; LIMBS]);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed([0; LIMBS]);
+
+    /// Converts `value` exactly, apart from bits below the precision of `Fixed`. Values
+    /// outside of the integer part saturate.
+    pub fn from_f64(value: f64) -> Self {
+        let mut rest = value.abs().min(i32::MAX as f64);
+        let mut limbs = [0; LIMBS];
+        // scaling by powers of two and taking the integer part off are both exact
+        for limb in limbs.iter_mut().rev() {
+            *limb = rest as u32;
+            rest = (rest - *limb as f64) * 4294967296.;
+        }
+        if value < 0. {
+            -Fixed(limbs)
+        } else {
+            Fixed(limbs)
+        }
+    }
+
+    /// The nearest `f64`, rounded towards zero.
+    pub fn to_f64(self) -> f64 {
+        let mut value = 0.;
+        let mut scale = 1.;
+        for &limb in self.abs().0.iter().rev() {
+            value += limb as f64 * scale;
+            scale /= 4294967296.;
+        }
+        if self.is_negative() {
+            -value
+        } else {
+            value
+        }
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0[LIMBS - 1] >> 31 == 1
+    }
+
+    pub fn abs(self) -> Self {
+        if self.is_negative() {
+            -self
+        } else {
+            self
+        }
+    }
+
+    /// The fraction `0.d₁d₂d₃…` of `digits` in `0..10`, rounded to the nearest `Fixed`.
+    fn from_decimals(digits: &[u32]) -> Self {
+        // divides from the last digit on, with a limb below the precision so the rounding
+        // errors of the divisions stay out of the result
+        let mut fraction = [0u32; LIMBS];
+        for &digit in digits.iter().rev() {
+            let mut remainder = digit as u64;
+            for limb in fraction.iter_mut().rev() {
+                let current = (remainder << 32) | *limb as u64;
+                *limb = (current / 10) as u32;
+                remainder = current % 10;
+            }
+        }
+        let mut limbs = [0; LIMBS];
+        limbs[..LIMBS - 1].copy_from_slice(&fraction[1..]);
+        let mut result = Fixed(limbs);
+        if fraction[0] >> 31 == 1 {
+            let mut ulp = [0; LIMBS];
+            ulp[0] = 1;
+            result = result + Fixed(ulp);
+        }
+        result
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        let mut limbs = [0; LIMBS];
+        let mut carry = false;
+        for (limb, (&a, &b)) in limbs.iter_mut().zip(self.0.iter().zip(&other.0)) {
+            let (sum, overflow_a) = a.overflowing_add(b);
+            let (sum, overflow_b) = sum.overflowing_add(carry as u32);
+            *limb = sum;
+            carry = overflow_a || overflow_b;
+        }
+        Fixed(limbs)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        let mut one = [0; LIMBS];
+        one[0] = 1;
+        Fixed(self.0.map(|limb| !limb)) + Fixed(one)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        self + -other
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    /// Multiplies the magnitudes and truncates the product, so it's rounded towards zero.
+    fn mul(self, other: Fixed) -> Fixed {
+        let (a, b) = (self.abs().0, other.abs().0);
+        let mut product = [0u32; 2 * LIMBS];
+        for (i, &a) in a.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in b.iter().enumerate() {
+                let sum = a as u64 * b as u64 + product[i + j] as u64 + carry;
+                product[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            product[i + LIMBS] = carry as u32;
+        }
+        // the product has twice the fraction limbs, the lower ones are dropped
+        let mut limbs = [0; LIMBS];
+        limbs.copy_from_slice(&product[LIMBS - 1..2 * LIMBS - 1]);
+        if self.is_negative() != other.is_negative() {
+            -Fixed(limbs)
+        } else {
+            Fixed(limbs)
+        }
+    }
+}
+
+/// Prints all [`DECIMALS`] without trailing zeros, or as many as the precision asks for.
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let decimals = f.precision().unwrap_or(DECIMALS);
+        // round half up at the last decimal
+        let mut half = vec![0; decimals + 1];
+        half[decimals] = 5;
+        let mut magnitude = self.abs() + Fixed::from_decimals(&half);
+
+        let mut text = magnitude.0[LIMBS - 1].to_string();
+        if decimals > 0 {
+            text.push('.');
+            for _ in 0..decimals {
+                magnitude.0[LIMBS - 1] = 0;
+                let mut carry = 0;
+                for limb in &mut magnitude.0 {
+                    let product = *limb as u64 * 10 + carry;
+                    *limb = product as u32;
+                    carry = product >> 32;
+                }
+                text.push(char::from_digit(magnitude.0[LIMBS - 1], 10).unwrap());
+            }
+        }
+        if f.precision().is_none() {
+            text = text.trim_end_matches('0').trim_end_matches('.').to_string();
+        }
+        f.pad_integral(!self.is_negative(), "", &text)
+    }
+}
+
+impl FromStr for Fixed {
+    type Err = String;
+
+    /// Parses decimal numbers like `-0.743643887037158704752191506114774`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let is_decimal = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.len() + fraction.len() == 0 || !is_decimal(integer) || !is_decimal(fraction) {
+            return Err(format!("{text:?} isn't a decimal number"));
+        }
+        let integer = match integer {
+            "" => 0,
+            integer => integer
+                .parse::<u32>()
+                .ok()
+                .filter(|&integer| integer <= i32::MAX as u32)
+                .ok_or_else(|| format!("{text:?} is too large"))?,
+        };
+
+        let digits: Vec<u32> = fraction.bytes().map(|byte| (byte - b'0') as u32).collect();
+        let mut magnitude = [0; LIMBS];
+        magnitude[LIMBS - 1] = integer;
+        let value = Fixed(magnitude) + Fixed::from_decimals(&digits);
+        Ok(if negative { -value } else { value })
+    }
+}
+
+/// Written as a decimal string, which JSON numbers couldn't hold exactly.
+impl Serialize for Fixed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Reads the decimal strings [`Fixed`] is written as, and plain numbers, which views were
+/// saved with before.
+impl<'de> Deserialize<'de> for Fixed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Fixed;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number or a decimal string")
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Fixed, E> {
+                Ok(Fixed::from_f64(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Fixed, E> {
+                Ok(Fixed::from_f64(value as f64))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Fixed, E> {
+                Ok(Fixed::from_f64(value as f64))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Fixed, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_convert_exactly() {
+        for value in [
+            0.,
+            1.,
+            -1.,
+            0.1,
+            -0.743643887037158,
+            1e-30,
+            -2f64.powi(-150),
+            12345.678,
+        ] {
+            assert_eq!(Fixed::from_f64(value).to_f64(), value);
+        }
+    }
+
+    #[test]
+    fn arithmetic_matches_doubles() {
+        // few enough bits that the doubles are exact too
+        let values = [0.5, -0.75, 1.25, -1.875, 0.3125, 3.];
+        for a in values {
+            for b in values {
+                let (fa, fb) = (Fixed::from_f64(a), Fixed::from_f64(b));
+                assert_eq!((fa + fb).to_f64(), a + b);
+                assert_eq!((fa - fb).to_f64(), a - b);
+                assert_eq!((fa * fb).to_f64(), a * b);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_what_doubles_lose() {
+        let tiny = Fixed::from_f64(2f64.powi(-150));
+        let sum = Fixed::from_f64(1.) + tiny;
+        assert_ne!(sum, Fixed::from_f64(1.));
+        assert_eq!((sum - Fixed::from_f64(1.)).to_f64(), 2f64.powi(-150));
+        // (1 + ε)² = 1 + 2ε + ε²
+        let square = (sum * sum - Fixed::from_f64(1.)).to_f64();
+        assert_eq!(square, 2f64.powi(-149));
+    }
+
+    #[test]
+    fn decimals_round_trip() {
+        let text = "-0.7436438870371587047521915061147740000000000000001";
+        let value: Fixed = text.parse().unwrap();
+        assert_eq!(value.to_string().parse::<Fixed>().unwrap(), value);
+        for value in [0., 0.5, -1.25, 0.1, -0.743643887037158, 1e-40] {
+            let value = Fixed::from_f64(value);
+            assert_eq!(value.to_string().parse::<Fixed>().unwrap(), value);
+        }
+        assert_eq!(Fixed::from_f64(-1.25).to_string(), "-1.25");
+        assert_eq!(Fixed::ZERO.to_string(), "0");
+    }
+
+    #[test]
+    fn precision_rounds_the_last_decimal() {
+        assert_eq!(format!("{:.3}", Fixed::from_f64(-0.7)), "-0.700");
+        assert_eq!(format!("{:.2}", Fixed::from_f64(0.125)), "0.13");
+        assert_eq!(format!("{:.0}", Fixed::from_f64(2.5)), "3");
+        assert_eq!(format!("{:.1}", Fixed::from_f64(9.99)), "10.0");
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_decimal() {
+        for text in ["", "-", ".", "1e5", "0x10", "1.2.3", "--1", "3000000000"] {
+            assert!(text.parse::<Fixed>().is_err(), "{text:?}");
+        }
+        assert_eq!(".5".parse::<Fixed>(), Ok(Fixed::from_f64(0.5)));
+    }
+
+    // JSON only comes with the app
+    #[cfg(feature = "gui")]
+    #[test]
+    fn reads_old_numbers_and_new_strings() {
+        let read = |json: &str| serde_json::from_str::<Fixed>(json).unwrap();
+        assert_eq!(read("-0.75"), Fixed::from_f64(-0.75));
+        assert_eq!(read("2"), Fixed::from_f64(2.));
+        let value = Fixed::from_f64(0.1) + Fixed::from_f64(2f64.powi(-140));
+        assert_eq!(read(&serde_json::to_string(&value).unwrap()), value);
+    }
+}
@@ -3,52 +3,334 @@ precision highp float;
 uniform vec2 center;
 uniform vec2 window_offset;
 uniform float zoom;
+// in degrees, counterclockwise about the center of the view
+uniform float rotation;
 uniform vec2 resolution;
 uniform int cycles;
-uniform vec3 start_color;
-uniform vec3 end_color;
+// the palette, from the color of param 0 in the first texel to that of 1 in the last one
+uniform highp sampler2D gradient;
+uniform bool convergent;
+uniform bool period_checking;
+// iterate the Mandelbrot as an offset from `reference_orbit`, see `UniformData::perturbation`
+uniform bool perturbation;
+// the orbit of the point `center` is measured from, computed in fixed point
+uniform highp sampler2D reference_orbit;
+uniform int reference_length;
+// iterate the Mandelbrot in pairs of floats, see `UniformData::double_float`
+uniform bool double_float;
+// `UniformData::origin` split into pairs of floats, the rounded values in xy, the errors in zw
+uniform vec4 origin;
+uniform int newton_degree;
+uniform float phoenix_p;
+// free parameters for custom functions, like the constants of a Julia set
+uniform vec2 constant_a;
+uniform vec2 constant_b;
+uniform float blend;
+// first iterate of the Mandelbrot and Phoenix orbits, 0 for the usual sets
+uniform vec2 z_seed;
+// progress of a transition between two fractal functions, see `morph_function`
+uniform float morph;
+uniform int coloring_mode;
+uniform int color_bands;
+uniform float color_offset;
+uniform bool cyclic_gradient;
+uniform int smoothing;
+// escape iterations at the start and end of the gradient
+uniform vec2 color_range;
+uniform int output_layer;
+uniform bool dither;
+uniform int antialiasing;
+// per channel grading of the final color, 0 and 1 leave it as it is
+uniform vec3 brightness;
+uniform vec3 contrast;
+// keep graded colors outside of 0..1, for floating point targets
+uniform bool unclamped;
+// write zero alpha for points in the set
+uniform bool transparent_interior;
 
 out vec4 fragColor;
 
 #define PI 3.14159265
 
-vec3 hsv2rgb(vec3 c) {
-    vec4 K = vec4(1., 2. / 3., 1. / 3., 3.);
-    vec3 p = abs(fract(c.xxx + K.xyz) * 6.0 - K.www);
-    return c.z * mix(K.xxx, clamp(p - K.xxx, 0.0, 1.0), c.y);
+// distance under which an orbit counts as back at an earlier iterate
+#define PERIOD_EPSILON 1e-6
+
+// width of `reference_orbit`, see `REFERENCE_WIDTH` in `renderer.rs`
+#define REFERENCE_WIDTH 1024
+
+// values of `coloring_mode`
+#define ESCAPE_TIME 0
+#define ORBIT_AVERAGE 1
+#define FINAL_ANGLE 2
+
+// values of `output_layer`
+#define COLOR 0
+#define ITERATIONS 1
+#define SET_MASK 2
+
+// values of `antialiasing`
+#define NO_ANTIALIASING 0
+#define DERIVATIVE 1
+#define SUPERSAMPLE 2
+
+// change of the escape count between neighboring pixels over which `DERIVATIVE` blends a pixel
+// fully with its neighbors, smaller changes blend less
+#define EDGE_ITERATIONS 4.
+
+// values of `smoothing`
+#define LINEAR 0
+#define CONTINUOUS 1
+#define SQUARE_ROOT 2
+#define LOGARITHMIC 3
+
+// what data layers write instead of a color, `inside` is set for points
+// that neither escaped nor converged
+vec4 layer_value(int i, bool inside) {
+    if (output_layer == ITERATIONS) {
+        // the count read back from a 16-bit red channel
+        return vec4(float(i) / 65535., 0., 0., 1.);
+    }
+    return inside ? vec4(1.) : vec4(0.);
 }
 
 vec4 get_color(float param) {
-    return vec4(hsv2rgb(mix(start_color, end_color, param)), 1.);
+    if (color_offset != 0.) {
+        // shift along the gradient, wrapping around when it repeats
+        param = cyclic_gradient ? fract(param + color_offset) : min(param + color_offset, 1.);
+    }
+    if (color_bands > 0) {
+        // snap to one of `color_bands` discrete steps
+        param = floor(param * float(color_bands)) / float(color_bands);
+    }
+    // the centers of the end texels hold the exact end colors
+    float size = float(textureSize(gradient, 0).x);
+    return vec4(texture(gradient, vec2((param * (size - 1.) + 0.5) / size, 0.5)).rgb, 1.);
+}
+
+// stretches each channel about the middle by `contrast`, then shifts it by `brightness`
+vec4 graded(vec4 color) {
+    color.rgb = (color.rgb - 0.5) * contrast + 0.5 + brightness;
+    if (!unclamped) {
+        color.rgb = clamp(color.rgb, 0., 1.);
+    }
+    return color;
 }
 
-vec2 iteration(vec2 previous_z, vec2 original_z) {
-    vec2 z;
-    z.x = previous_z.x * previous_z.x - previous_z.y * previous_z.y + original_z.x;
-    z.y = 2. * previous_z.x * previous_z.y + original_z.y;
+// adds noise of about one 8-bit step when `dither` is set, so gradients don't show bands
+vec4 dithered(vec4 color) {
+    if (!dither) {
+        return color;
+    }
+    // interleaved gradient noise, evenly spread like blue noise and cheap to compute
+    float noise = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    color.rgb += (noise - 0.5) / 255.;
+    return color;
+}
 
-    return z;
+// maps the escape iteration into 0..1 according to `smoothing`
+float normalize_escape(int i, vec2 z) {
+    float n = float(i) - color_range.x;
+    float max_n = color_range.y - color_range.x;
+    if (smoothing == CONTINUOUS) {
+        // fractional escape count, removes the bands between iterations
+        return clamp((n + 1. - log2(log(length(z)))) / max_n, 0., 1.);
+    }
+    // escapes outside of the range get the color of its ends
+    n = clamp(n, 0., max_n);
+    if (smoothing == SQUARE_ROOT) {
+        return sqrt(n / max_n);
+    }
+    if (smoothing == LOGARITHMIC) {
+        return log(1. + n) / log(1. + max_n);
+    }
+    return n / max_n;
 }
 
-void main() {
-    vec2 pos = ((gl_FragCoord.xy - window_offset) / resolution) - 0.5;
-    pos.y *= -1.; // invert Y axis (opengl has 0,0 at bottom left corner, egui at top left)
-    pos += center; // shift center acc to zoom
+// maps an escaped orbit to the gradient according to `coloring_mode`
+float escape_param(int i, vec2 z, float orbit_sum) {
+    if (coloring_mode == ORBIT_AVERAGE) {
+        return clamp(orbit_sum / float(i + 1) / 2., 0., 1.);
+    }
+    if (coloring_mode == FINAL_ANGLE) {
+        return atan(z.y, z.x) / PI / 2. + 0.5;
+    }
+    return normalize_escape(i, z);
+}
+
+vec2 complex_mul(vec2 a, vec2 b) {
+    return vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+vec2 complex_div(vec2 a, vec2 b) {
+    return vec2(a.x * b.x + a.y * b.y, a.y * b.x - a.x * b.y) / dot(b, b);
+}
+
+vec2 complex_pow(vec2 z, int exponent) {
+    vec2 result = vec2(1., 0.);
+    for (int i = 0; i < exponent; i++) {
+        result = complex_mul(result, z);
+    }
+    return result;
+}
+
+// the iterate before `previous_z`, for fractals that depend on two previous iterations
+vec2 older_z = vec2(0., 0.);
+
+// index of the current iteration, starting at 0
+int iteration_index = 0;
+
+// position of the pixel in the view, (0, 0) is the top left corner and (1, 1) the bottom right
+vec2 screen_position = vec2(0., 0.);
+
+// iterate `index` of `reference_orbit`
+vec2 reference_point(int index) {
+    return texelFetch(reference_orbit, ivec2(index % REFERENCE_WIDTH, index / REFERENCE_WIDTH), 0).xy;
+}
+
+// defined by the fractal function appended to this file
+vec2 iteration(vec2 previous_z, vec2 original_z);
+
+// defined by `double_float.glsl`, which is appended before the fractal function
+vec2 df_add(vec2 a, vec2 b);
+vec2 df_mul(vec2 a, vec2 b);
+
+// the color of the point at `frag_coord` in window coordinates, or what `output_layer` asks for,
+// and the continuous count of iterations it took to escape or converge, `cycles` inside
+vec4 shade(vec2 frag_coord, out float count) {
+    older_z = vec2(0., 0.);
+    iteration_index = 0;
+    count = float(cycles);
+
+    vec2 offset = ((frag_coord - window_offset) / resolution) - 0.5;
+    offset.y *= -1.; // invert Y axis (opengl has 0,0 at bottom left corner, egui at top left)
+    vec2 aspect = vec2(1., resolution.y / resolution.x); // fix squishing in non-square aspect ratio
+    float angle = radians(rotation);
+    mat2 rotate = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    vec2 pos = center * aspect + rotate * (offset * aspect); // shift center acc to zoom
     pos /= zoom; // scale pos according to `zoom`
-    pos.y *= resolution.y / resolution.x; // fix squishing in non-square aspect ratio
+
+    screen_position = (frag_coord - window_offset) / resolution;
+    screen_position.y = 1. - screen_position.y;
 
     vec2 z = pos;
+    // with `perturbation`, `pos` is the offset from the reference point, the iterates are
+    // the reference orbit plus `delta`, and only `delta` needs the precision
+    vec2 delta = pos;
+    int reference_index = 1;
+    if (perturbation) {
+        z = reference_point(reference_index) + delta;
+    }
+    // with `double_float`, `pos` is the offset from `origin` and the real and imaginary
+    // parts of the point and the iterates are pairs of floats, `z` only gets the rounded values
+    vec2 c_x = df_add(origin.xz, vec2(pos.x, 0.));
+    vec2 c_y = df_add(origin.yw, vec2(pos.y, 0.));
+    vec2 z_x = c_x;
+    vec2 z_y = c_y;
+    if (double_float) {
+        z = vec2(z_x.x, z_y.x);
+    }
+    float orbit_sum = 0.;
+    // iterate the orbit is compared against when `period_checking` is set
+    vec2 period_reference = z;
+    int next_reference = 0;
 
     for (int i = 0; i < cycles; i++) {
-        z = iteration(z, pos);
+        vec2 previous_z = z;
+        iteration_index = i;
+        if (perturbation) {
+            vec2 reference = reference_point(reference_index);
+            delta = 2. * complex_mul(reference, delta) + complex_mul(delta, delta) + pos;
+            reference_index++;
+            z = reference_point(reference_index) + delta;
+            // start over from the beginning of the reference orbit, which is zero, once the
+            // orbit comes closer to zero than to the reference or the reference runs out
+            if (dot(z, z) < dot(delta, delta) || reference_index == reference_length - 1) {
+                delta = z;
+                reference_index = 0;
+            }
+        } else if (double_float) {
+            vec2 x_squared = df_mul(z_x, z_x);
+            vec2 y_squared = df_mul(z_y, z_y);
+            vec2 xy = df_mul(z_x, z_y);
+            z_x = df_add(df_add(x_squared, -y_squared), c_x);
+            z_y = df_add(2. * xy, c_y);
+            z = vec2(z_x.x, z_y.x);
+        } else {
+            z = iteration(z, pos);
+        }
+        older_z = previous_z;
+        orbit_sum += length(z);
 
-        if (z.x * z.x + z.y * z.y > 4.0) {
-            float param = float(i) / float(cycles);
-            fragColor = get_color(param);
-            return;
+        if (convergent) {
+            // color by the root the orbit settled in, darken by how long it took
+            if (distance(z, previous_z) < 1e-5) {
+                count = float(i);
+                if (output_layer != COLOR) {
+                    return layer_value(i, false);
+                }
+                float root = atan(z.y, z.x) / PI / 2. + 0.5;
+                vec4 color = get_color(root);
+                // by the color range rather than `cycles`, like the escape time
+                float steps = (float(i) - color_range.x) / (color_range.y - color_range.x);
+                color.rgb *= 1. - clamp(steps, 0., 1.);
+                return graded(color);
+            }
+        } else if (z.x * z.x + z.y * z.y > 4.0) {
+            count = float(i) + 1. - log2(log(length(z)));
+            if (output_layer != COLOR) {
+                return layer_value(i, false);
+            }
+            return graded(get_color(escape_param(i, z, orbit_sum)));
+        }
+
+        if (period_checking && !convergent) {
+            // the orbit is periodic, so it never escapes
+            if (distance(z, period_reference) < PERIOD_EPSILON) {
+                break;
+            }
+            // compare against iterates further and further back, like Brent's algorithm,
+            // so cycles of any length are found
+            if (i == next_reference) {
+                period_reference = z;
+                next_reference = 2 * i + 1;
+            }
         }
     }
 
+    if (output_layer != COLOR) {
+        return layer_value(cycles, true);
+    }
     float param = atan(z.y, z.x) / PI / 2. + 0.5;
-    fragColor = get_color(param);
+    vec4 color = graded(get_color(param));
+    if (transparent_interior) {
+        color.a = 0.;
+    }
+    return color;
+}
+
+void main() {
+    float count;
+    if (output_layer != COLOR || antialiasing == NO_ANTIALIASING) {
+        fragColor = shade(gl_FragCoord.xy, count);
+    } else if (antialiasing == DERIVATIVE) {
+        fragColor = shade(gl_FragCoord.xy, count);
+        // blend pixels whose escape count changes quickly, which happens at the edge of the
+        // set, with their neighbors in the 2×2 block the derivatives are computed over
+        float edge = clamp(fwidth(count) / EDGE_ITERATIONS, 0., 1.);
+        vec2 side = 1. - 2. * mod(floor(gl_FragCoord.xy), 2.);
+        vec4 neighbors = fragColor + (side.x * dFdx(fragColor) + side.y * dFdy(fragColor)) / 3.;
+        fragColor = mix(fragColor, neighbors, edge);
+    } else {
+        // four samples on a rotated grid, which catches more edges than an aligned one
+        vec2 samples[4] = vec2[](
+            vec2(-0.125, -0.375), vec2(0.375, -0.125), vec2(0.125, 0.375), vec2(-0.375, 0.125)
+        );
+        fragColor = vec4(0.);
+        for (int i = 0; i < 4; i++) {
+            fragColor += shade(gl_FragCoord.xy + samples[i], count) / 4.;
+        }
+    }
+    if (output_layer == COLOR) {
+        fragColor = dithered(fragColor);
+    }
 }
@@ -0,0 +1,469 @@
+//! Encodes animated GIFs, whose frames share one palette of at most 256 colors.
+
+use std::{collections::HashMap, io::Write};
+
+/// Most colors a GIF palette holds.
+pub const MAX_COLORS: usize = 256;
+
+/// Most samples the palette is picked from, the rest are skipped evenly.
+pub const PALETTE_SAMPLES: usize = 1 << 16;
+
+/// Codes the LZW table is filled up to before it's cleared. Like giflib, the last possible
+/// code, 4095, is left unused, which every decoder handles.
+const MAX_CODE: u16 = 4095;
+
+/// How the frames of a [`GifEncoder`] are reduced to the palette.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GifOptions {
+    /// Size of the palette, in `2..=`[`MAX_COLORS`].
+    pub colors: usize,
+    /// Spread the difference to the palette color onto the neighboring pixels with
+    /// Floyd–Steinberg dithering, which hides bands in smooth gradients.
+    pub dither: bool,
+    /// Time each frame is shown, in hundredths of a second.
+    pub delay: u16,
+}
+
+/// Picks up to `colors` colors representing `samples` by median cut. The box of samples
+/// with the widest range in one channel is split at its median along that channel until
+/// there are enough boxes or every box holds one color, then each box becomes the average of
+/// its samples.
+fn median_cut(samples: &mut [[u8; 3]], colors: usize) -> Vec<[u8; 3]> {
+    let range = |samples: &[[u8; 3]]| {
+        (0..3)
+            .map(|channel| {
+                let values = samples.iter().map(|sample| sample[channel]);
+                let (min, max) = (values.clone().min(), values.max());
+                (max.unwrap_or(0) - min.unwrap_or(0), channel)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+    let mut boxes = Vec::new();
+    boxes.push(0..samples.len());
+    while boxes.len() < colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, samples_range)| (range(&samples[samples_range.clone()]), i))
+            .filter(|&((width, _), _)| width > 0)
+            .max();
+        let Some(((_, channel), i)) = widest else {
+            // every box holds a single color
+            break;
+        };
+        let split = boxes[i].clone();
+        samples[split.clone()].sort_unstable_by_key(|sample| sample[channel]);
+        // split between two values, so that samples of one color stay in one box
+        let box_samples = &samples[split.clone()];
+        let median = box_samples[box_samples.len() / 2][channel];
+        let below = box_samples.partition_point(|sample| sample[channel] < median);
+        let middle = split.start
+            + if below > 0 {
+                below
+            } else {
+                box_samples.partition_point(|sample| sample[channel] <= median)
+            };
+        boxes[i] = split.start..middle;
+        boxes.push(middle..split.end);
+    }
+    boxes
+        .into_iter()
+        .filter(|samples_range| !samples_range.is_empty())
+        .map(|samples_range| {
+            let count = samples_range.len() as u64;
+            let mut sum = [0u64; 3];
+            for sample in &samples[samples_range] {
+                for channel in 0..3 {
+                    sum[channel] += sample[channel] as u64;
+                }
+            }
+            sum.map(|sum| ((sum + count / 2) / count) as u8)
+        })
+        .collect()
+}
+
+/// Finds the closest palette colors, remembering the ones found.
+struct NearestColor {
+    palette: Vec<[u8; 3]>,
+    known: HashMap<[u8; 3], u8>,
+}
+
+impl NearestColor {
+    fn new(palette: Vec<[u8; 3]>) -> Self {
+        Self {
+            palette,
+            known: HashMap::new(),
+        }
+    }
+
+    fn index(&mut self, color: [u8; 3]) -> u8 {
+        let palette = &self.palette;
+        *self.known.entry(color).or_insert_with(|| {
+            let distance = |entry: &[u8; 3]| {
+                (0..3)
+                    .map(|channel| (entry[channel] as i32 - color[channel] as i32).pow(2))
+                    .sum::<i32>()
+            };
+            (0..palette.len())
+                .min_by_key(|&i| distance(&palette[i]))
+                .unwrap_or(0) as u8
+        })
+    }
+}
+
+/// Maps the RGBA `pixels`, top row first, to indices into the palette of `nearest`, with
+/// Floyd–Steinberg dithering when `dither` is set. Alpha is ignored.
+fn index_pixels(pixels: &[u8], width: usize, nearest: &mut NearestColor, dither: bool) -> Vec<u8> {
+    let mut indices = Vec::with_capacity(pixels.len() / 4);
+    // errors carried to the current and the next row, with a pixel of room on both sides
+    let mut errors = vec![[0f32; 3]; width + 2];
+    let mut next_errors = vec![[0f32; 3]; width + 2];
+    for row in pixels.chunks_exact(width * 4) {
+        for (x, pixel) in row.chunks_exact(4).enumerate() {
+            let wanted = [0, 1, 2]
+                .map(|channel| (pixel[channel] as f32 + errors[x + 1][channel]).clamp(0., 255.));
+            let index = nearest.index(wanted.map(|value| value.round() as u8));
+            indices.push(index);
+            if dither {
+                let color = nearest.palette[index as usize];
+                for channel in 0..3 {
+                    let error = wanted[channel] - color[channel] as f32;
+                    errors[x + 2][channel] += error * 7. / 16.;
+                    next_errors[x][channel] += error * 3. / 16.;
+                    next_errors[x + 1][channel] += error * 5. / 16.;
+                    next_errors[x + 2][channel] += error / 16.;
+                }
+            }
+        }
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.fill([0.; 3]);
+    }
+    indices
+}
+
+/// Packs codes of varying length into bytes, least significant bit first, as GIF does.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses palette `indices` with the variable code length LZW of GIF, starting with
+/// codes of `min_code_size + 1` bits.
+fn lzw_compress(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = BitWriter::default();
+    let mut table = HashMap::new();
+    let mut size = min_code_size + 1;
+    let mut next = end + 1;
+    writer.write(clear, size);
+
+    let mut indices = indices.iter().copied();
+    let Some(first) = indices.next() else {
+        writer.write(end, size);
+        return writer.finish();
+    };
+    let mut prefix = first as u16;
+    for index in indices {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix, size);
+        // the decoder adds the entry for this code when it reads the next one, and widens
+        // its codes once they no longer fit
+        if next >= 1 << size && size < 12 {
+            size += 1;
+        }
+        if next == MAX_CODE {
+            writer.write(clear, size);
+            table.clear();
+            size = min_code_size + 1;
+            next = end + 1;
+        } else {
+            table.insert((prefix, index), next);
+            next += 1;
+        }
+        prefix = index as u16;
+    }
+    writer.write(prefix, size);
+    if next >= 1 << size && size < 12 {
+        size += 1;
+    }
+    writer.write(end, size);
+    writer.finish()
+}
+
+/// Writes a GIF that loops forever a frame at a time, so no more than one of them has to be
+/// kept around. The frames share a palette picked up front from samples of all of them, so
+/// colors don't jump between them.
+pub struct GifEncoder<W: Write> {
+    writer: W,
+    width: u16,
+    height: u16,
+    /// The color table holds `1 << table_bits` colors.
+    table_bits: u32,
+    nearest: NearestColor,
+    options: GifOptions,
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Picks the palette from `samples`, RGBA pixels taken from all frames, and writes
+    /// everything that comes before the first frame.
+    pub fn new(
+        writer: W,
+        width: u32,
+        height: u32,
+        samples: &[u8],
+        options: GifOptions,
+    ) -> Result<Self, String> {
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(format!("GIFs can't be larger than {0}x{0}", u16::MAX));
+        };
+        if !(2..=MAX_COLORS).contains(&options.colors) {
+            return Err(format!("GIF palettes have 2 to {MAX_COLORS} colors"));
+        }
+
+        let step = (samples.len() / 4).div_ceil(PALETTE_SAMPLES).max(1);
+        let mut samples: Vec<[u8; 3]> = samples
+            .chunks_exact(4)
+            .step_by(step)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        let mut palette = median_cut(&mut samples, options.colors);
+        // the table holds a power of two colors, at least the four LZW needs
+        let table_bits = palette.len().max(4).next_power_of_two().trailing_zeros();
+        palette.resize(1 << table_bits, [0; 3]);
+
+        let mut data = b"GIF89a".to_vec();
+        data.extend(width.to_le_bytes());
+        data.extend(height.to_le_bytes());
+        // global color table with 8 bits per primary
+        data.extend([0xf0 | (table_bits - 1) as u8, 0, 0]);
+        data.extend(palette.iter().flatten());
+        // loop forever
+        data.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        let mut encoder = Self {
+            writer,
+            width,
+            height,
+            table_bits,
+            nearest: NearestColor::new(palette),
+            options,
+        };
+        encoder.write(&data)?;
+        Ok(encoder)
+    }
+
+    /// Reduces a frame of RGBA pixels, top row first, to the palette and writes it.
+    pub fn add_frame(&mut self, pixels: &[u8]) -> Result<(), String> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        if pixels.len() != width * height * 4 {
+            return Err(format!("GIF frames have to be {width}x{height}"));
+        }
+        // graphic control extension, frames replace each other without transparency
+        let mut data = vec![0x21, 0xf9, 0x04, 0x04];
+        data.extend(self.options.delay.to_le_bytes());
+        data.extend([0, 0]);
+        // image descriptor of the whole canvas, using the global color table
+        data.push(0x2c);
+        data.extend([0u8; 4]);
+        data.extend(self.width.to_le_bytes());
+        data.extend(self.height.to_le_bytes());
+        data.push(0);
+
+        let indices = index_pixels(pixels, width, &mut self.nearest, self.options.dither);
+        data.push(self.table_bits as u8);
+        for block in lzw_compress(&indices, self.table_bits).chunks(255) {
+            data.push(block.len() as u8);
+            data.extend(block);
+        }
+        data.push(0);
+        self.write(&data)
+    }
+
+    /// Ends the GIF after the last frame and hands back the writer.
+    pub fn finish(mut self) -> Result<W, String> {
+        self.write(&[0x3b])?;
+        self.writer
+            .flush()
+            .map_err(|e| format!("Cannot write the GIF: {e}"))?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.writer
+            .write_all(data)
+            .map_err(|e| format!("Cannot write the GIF: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes what [`lzw_compress`] wrote, the way GIF decoders do.
+    fn lzw_decompress(data: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> {
+            let mut table: Vec<Vec<u8>> = (0..clear).map(|i| vec![i as u8]).collect();
+            table.extend([vec![], vec![]]);
+            table
+        };
+        let mut table = reset();
+        let mut size = min_code_size + 1;
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0, data.iter());
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        loop {
+            while bits < size {
+                buffer |= (*bytes.next().expect("the data ends early") as u32) << bits;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << size) - 1)) as usize;
+            buffer >>= size;
+            bits -= size;
+            if code == clear {
+                table = reset();
+                size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return output;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.clone(), vec![previous[0]]].concat(),
+                (None, None) => panic!("code {code} isn't in the table"),
+            };
+            output.extend(&entry);
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    table.push([previous, vec![entry[0]]].concat());
+                }
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips() {
+        let long: Vec<u8> = (0..100_000u64).map(|i| ((i * i) >> 7) as u8 % 16).collect();
+        for (indices, min_code_size) in [
+            (vec![], 2),
+            (vec![3], 2),
+            (vec![0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2], 2),
+            (long, 4),
+            ((0..=255).cycle().take(70_000).collect(), 8),
+        ] {
+            let compressed = lzw_compress(&indices, min_code_size);
+            assert_eq!(lzw_decompress(&compressed, min_code_size), indices);
+        }
+    }
+
+    #[test]
+    fn few_colors_are_kept_exactly() {
+        let colors = [[255, 0, 0], [0, 0, 255], [10, 200, 30]];
+        let mut samples: Vec<[u8; 3]> = colors.iter().cycle().take(300).copied().collect();
+        let mut palette = median_cut(&mut samples, 16);
+        palette.sort();
+        let mut expected = colors.to_vec();
+        expected.sort();
+        assert_eq!(palette, expected);
+    }
+
+    #[test]
+    fn median_cut_fills_the_palette() {
+        let mut samples: Vec<[u8; 3]> = (0..=255).map(|v| [v, 255 - v, v / 2]).collect();
+        assert_eq!(median_cut(&mut samples, 16).len(), 16);
+    }
+
+    #[test]
+    fn dithering_keeps_the_average() {
+        let palette = [[0, 0, 0], [255, 255, 255]];
+        let gray: Vec<u8> = [100, 100, 100, 255].repeat(64 * 64);
+        for (dither, expected) in [(false, 0.), (true, 100.)] {
+            let indices = index_pixels(&gray, 64, &mut NearestColor::new(palette.to_vec()), dither);
+            let average = indices
+                .iter()
+                .map(|&i| palette[i as usize][0] as f32)
+                .sum::<f32>()
+                / indices.len() as f32;
+            assert!((average - expected).abs() < 2., "{average}");
+        }
+    }
+
+    #[test]
+    fn gifs_have_the_frames_and_loop() {
+        let frame: Vec<u8> = (0..16 * 8)
+            .flat_map(|i| [i as u8, 0, 255 - i as u8, 255])
+            .collect();
+        let options = GifOptions {
+            colors: 8,
+            dither: true,
+            delay: 4,
+        };
+        let mut encoder = GifEncoder::new(Vec::new(), 16, 8, &frame, options).unwrap();
+        encoder.add_frame(&frame).unwrap();
+        encoder.add_frame(&frame).unwrap();
+        assert!(encoder.add_frame(&frame[4..]).is_err());
+        let gif = encoder.finish().unwrap();
+        assert!(gif.starts_with(b"GIF89a\x10\x00\x08\x00"));
+        let table_size = 3 << ((gif[10] & 7) + 1);
+        assert_eq!(table_size, 3 * 8);
+        let mut rest = &gif[13 + table_size..];
+        assert!(rest.starts_with(b"\x21\xff\x0bNETSCAPE2.0"));
+        rest = &rest[19..];
+        let mut frames = 0;
+        while rest[0] == 0x21 {
+            assert_eq!(&rest[..6], &[0x21, 0xf9, 0x04, 0x04, 4, 0]);
+            assert_eq!(rest[8], 0x2c);
+            let min_code_size = rest[18] as u32;
+            rest = &rest[19..];
+            let mut data = Vec::new();
+            while rest[0] != 0 {
+                data.extend(&rest[1..=rest[0] as usize]);
+                rest = &rest[rest[0] as usize + 1..];
+            }
+            rest = &rest[1..];
+            assert_eq!(lzw_decompress(&data, min_code_size).len(), 16 * 8);
+            frames += 1;
+        }
+        assert_eq!(frames, 2);
+        assert_eq!(rest, [0x3b]);
+        assert!(GifEncoder::new(Vec::new(), 70_000, 1, &[], options).is_err());
+        let one_color = GifOptions {
+            colors: 1,
+            ..options
+        };
+        assert!(GifEncoder::new(Vec::new(), 1, 1, &[], one_color).is_err());
+    }
+}
@@ -0,0 +1,31 @@
+//! Renders escape-time fractals with OpenGL, see [`renderer::render_frame`] for the simplest
+//! way to get an image. The `gui` feature (on by default) adds the [`App`] frontend.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench;
+pub mod fixed;
+pub mod orbit;
+pub mod palette;
+pub mod renderer;
+pub mod uniforms;
+pub mod view;
+
+#[cfg(feature = "gui")]
+mod app;
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+pub mod batch;
+#[cfg(feature = "gui")]
+mod export;
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+mod gif;
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+mod watch;
+
+#[cfg(feature = "gui")]
+pub use app::{App, VSYNC_ENV};
+pub use renderer::{
+    fragment_shader_source, render_frame, FractalType, Renderer, ShaderError, ALTERNATING_FUNC,
+    BLENDED_JULIA_FUNC, BURNING_SHIP_FUNC, CELTIC_FUNC, EXAMPLE_FUNCS, MAGNET_FUNC,
+    MANDELBROT_FUNC, MULTIBROT_FUNC, NEWTON_FUNC, PHOENIX_FUNC, TRICORN_FUNC,
+};
+pub use uniforms::UniformData;
@@ -1,18 +1,57 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-mod app;
-mod renderer;
-pub use app::App;
+use fractalgui::App;
 
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
     env_logger::init();
-    let native_options = eframe::NativeOptions::default();
+    if std::env::args().any(|arg| arg == "--bench") {
+        return eframe::run_native(
+            "FractalGUI benchmark",
+            eframe::NativeOptions {
+                vsync: false,
+                ..Default::default()
+            },
+            Box::new(|_| Ok(Box::<fractalgui::bench::Benchmark>::default())),
+        );
+    }
+    if std::env::args().any(|arg| arg == "--batch") {
+        let options = match fractalgui::batch::BatchOptions::parse(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(error) => {
+                eprintln!("{error}\n{}", fractalgui::batch::USAGE);
+                std::process::exit(2);
+            }
+        };
+        #[cfg(not(target_os = "macos"))]
+        match fractalgui::batch::HeadlessContext::new() {
+            Ok(context) => {
+                fractalgui::batch::run_batch_or_exit(context.gl(), &options);
+                return Ok(());
+            }
+            Err(error) => log::info!("Rendering in a window, no headless context: {error}"),
+        }
+        return eframe::run_native(
+            "FractalGUI batch",
+            eframe::NativeOptions {
+                vsync: false,
+                ..Default::default()
+            },
+            Box::new(|_| Ok(Box::new(fractalgui::batch::Batch::new(options)))),
+        );
+    }
+    let native_options = eframe::NativeOptions {
+        // eframe saves the window size and position into its storage, and moves the
+        // window back onto a monitor if the saved position is no longer visible
+        persist_window: true,
+        vsync: std::env::var(fractalgui::VSYNC_ENV).map_or(true, |vsync| vsync != "0"),
+        ..Default::default()
+    };
     eframe::run_native(
         "FractalGUI",
         native_options,
-        Box::new(|cc| Ok(Box::new(App::new(cc)))),
+        Box::new(|cc| Ok(App::create(cc))),
     )
 }
 
@@ -31,7 +70,7 @@ fn main() {
             .start(
                 "the_canvas_id",
                 web_options,
-                Box::new(|cc| Ok(Box::new(App::new(cc)))),
+                Box::new(|cc| Ok(App::create(cc))),
             )
             .await;
 
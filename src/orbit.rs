@@ -0,0 +1,100 @@
+use egui::{vec2, Vec2};
+
+use crate::{fixed::Fixed, renderer::FractalType, uniforms::UniformData};
+
+/// How the orbit of a point ended, as decided by `frag.glsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orbit {
+    /// Left the radius 2 circle at the given iteration.
+    Escaped(i32),
+    /// Settled on a root at the given iteration, for convergent fractals.
+    Converged(i32),
+    /// Did neither within `cycles` iterations.
+    Bounded,
+}
+
+fn complex_mul(a: Vec2, b: Vec2) -> Vec2 {
+    vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x)
+}
+
+fn complex_div(a: Vec2, b: Vec2) -> Vec2 {
+    vec2(a.x * b.x + a.y * b.y, a.y * b.x - a.x * b.y) / b.length_sq()
+}
+
+fn complex_pow(z: Vec2, exponent: i32) -> Vec2 {
+    (0..exponent).fold(vec2(1., 0.), |result, _| complex_mul(result, z))
+}
+
+/// Follows the orbit of `point` (in fractal coordinates) on the CPU, like the shader does.
+/// Returns `None` for custom functions, those only exist as GLSL.
+pub fn orbit(fractal_type: FractalType, uniform_data: &UniformData, point: Vec2) -> Option<Orbit> {
+    if fractal_type == FractalType::Custom {
+        return None;
+    }
+    let degree = uniform_data.newton_degree;
+    let step = |z: Vec2, older_z: Vec2| match fractal_type {
+        FractalType::Mandelbrot => complex_mul(z, z) + point,
+        FractalType::Newton => {
+            let f = complex_pow(z, degree) - vec2(1., 0.);
+            let df = degree as f32 * complex_pow(z, degree - 1);
+            z - complex_div(f, df)
+        }
+        FractalType::Phoenix => complex_mul(z, z) + point + uniform_data.phoenix_p * older_z,
+        FractalType::Custom => unreachable!("custom functions are handled above"),
+    };
+
+    let seed = uniform_data.z_seed;
+    // the seeded fractals start at `z_seed`, the first iterate is computed here
+    let (mut z, mut older_z) = match fractal_type {
+        FractalType::Mandelbrot | FractalType::Phoenix => (complex_mul(seed, seed) + point, seed),
+        _ => (point, Vec2::ZERO),
+    };
+    let mut period_reference = z;
+    let mut next_reference = 0;
+    for i in 0..uniform_data.cycles {
+        let previous_z = z;
+        z = step(z, older_z);
+        older_z = previous_z;
+
+        if uniform_data.convergent {
+            if (z - previous_z).length() < 1e-5 {
+                return Some(Orbit::Converged(i));
+            }
+        } else if z.length_sq() > 4. {
+            return Some(Orbit::Escaped(i));
+        }
+
+        if uniform_data.period_checking && !uniform_data.convergent {
+            if (z - period_reference).length() < 1e-6 {
+                break;
+            }
+            if i == next_reference {
+                period_reference = z;
+                next_reference = 2 * i + 1;
+            }
+        }
+    }
+    Some(Orbit::Bounded)
+}
+
+/// Iterates the Mandelbrot orbit of `point` in fixed point for the perturbation in
+/// `frag.glsl`, which stays precise however deep the view is. Starts at zero, so the shader
+/// can restart from the beginning of the orbit without losing precision, and stops after
+/// the first iterate outside of the radius 2 circle. The iterates are small enough to keep
+/// their precision as `f32`.
+pub fn reference_orbit(point: [Fixed; 2], cycles: i32) -> Vec<Vec2> {
+    let [cx, cy] = point;
+    let (mut x, mut y) = (Fixed::ZERO, Fixed::ZERO);
+    let mut orbit = vec![Vec2::ZERO];
+    // the shader starts at `point` and iterates `cycles` times from there
+    for _ in 0..=cycles {
+        let xy = x * y;
+        (x, y) = (x * x - y * y + cx, xy + xy + cy);
+        let z = vec2(x.to_f64() as f32, y.to_f64() as f32);
+        orbit.push(z);
+        if z.length_sq() > 4. {
+            break;
+        }
+    }
+    orbit
+}
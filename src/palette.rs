@@ -0,0 +1,489 @@
+use egui::{
+    ecolor::{gamma_from_linear, linear_from_gamma},
+    epaint::Hsva,
+};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::uniforms::{hsva_serde, Colormap, Interpolation};
+
+/// A named gradient that can be saved and applied to the view.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    #[serde(with = "hsva_serde")]
+    pub start_color: Hsva,
+    #[serde(with = "hsva_serde")]
+    pub end_color: Hsva,
+    /// Replaces the gradient between the two colors, which are then only its ends.
+    #[serde(default)]
+    pub colormap: Option<Colormap>,
+    /// Like `colormap`, the stops of a gradient imported from a file.
+    #[serde(default)]
+    pub stops: Option<GradientStops>,
+}
+
+impl Palette {
+    fn new(name: &str, start_color: Hsva, end_color: Hsva) -> Self {
+        Self {
+            name: name.to_string(),
+            start_color,
+            end_color,
+            colormap: None,
+            stops: None,
+        }
+    }
+
+    /// The palette of `colormap`, named after it.
+    pub fn from_colormap(colormap: Colormap) -> Self {
+        let stops = colormap_stops(colormap);
+        let color = |stop: [f32; 3]| rgb_to_hsva(stop[0], stop[1], stop[2]);
+        Self {
+            name: colormap.name().to_string(),
+            start_color: color(stops[0]),
+            end_color: color(stops[stops.len() - 1]),
+            colormap: Some(colormap),
+            stops: None,
+        }
+    }
+}
+
+/// The library before the user saves anything.
+pub fn builtin_palettes() -> Vec<Palette> {
+    vec![
+        Palette::new(
+            "Fire",
+            Hsva::new(0., 1., 0.3, 1.),
+            Hsva::new(0.15, 0.8, 1., 1.),
+        ),
+        Palette::new(
+            "Ocean",
+            Hsva::new(0.66, 1., 0.2, 1.),
+            Hsva::new(0.5, 0.5, 1., 1.),
+        ),
+        Palette::new(
+            "Grayscale",
+            Hsva::new(0., 0., 0., 1.),
+            Hsva::new(0., 0., 1., 1.),
+        ),
+        // blended in HSV, the hue goes through all of them
+        Palette::new(
+            "Rainbow",
+            Hsva::new(0., 1., 1., 1.),
+            Hsva::new(0.999, 1., 1., 1.),
+        ),
+    ]
+}
+
+/// Number of colors in the gradient texture the shader looks its colors up in.
+pub const GRADIENT_SIZE: usize = 1024;
+
+/// Converts HSV values to sRGB like the `hsv2rgb` function common in shaders.
+fn hsva_to_rgb(color: Hsva) -> [f32; 3] {
+    let channel = |k: f32| {
+        let x = color.h + k;
+        let p = ((x - x.floor()) * 6. - 3.).abs();
+        color.v * (1. + ((p - 1.).clamp(0., 1.) - 1.) * color.s)
+    };
+    [channel(1.), channel(2. / 3.), channel(1. / 3.)]
+}
+
+/// Samples the gradient from `start` to `end` at [`GRADIENT_SIZE`] evenly spaced points
+/// as opaque RGBA, for the texture the shader colors pixels from.
+pub fn bake_gradient(start: Hsva, end: Hsva, interpolation: Interpolation) -> Vec<[f32; 4]> {
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let linear = |color: Hsva| hsva_to_rgb(color).map(linear_from_gamma);
+    let (linear_start, linear_end) = (linear(start), linear(end));
+    (0..GRADIENT_SIZE)
+        .map(|i| {
+            let t = i as f32 / (GRADIENT_SIZE - 1) as f32;
+            let [r, g, b] = match interpolation {
+                Interpolation::Hsv => hsva_to_rgb(Hsva::new(
+                    lerp(start.h, end.h, t),
+                    lerp(start.s, end.s, t),
+                    lerp(start.v, end.v, t),
+                    1.,
+                )),
+                Interpolation::Rgb => {
+                    [0, 1, 2].map(|c| gamma_from_linear(lerp(linear_start[c], linear_end[c], t)))
+                }
+            };
+            [r, g, b, 1.]
+        })
+        .collect()
+}
+
+/// sRGB colors of the evenly spaced stops of `colormap`, from matplotlib for viridis and
+/// cividis and from ColorBrewer's RdBu, reversed, for blue–red.
+fn colormap_stops(colormap: Colormap) -> Vec<[f32; 3]> {
+    let stops: &[u32] = match colormap {
+        Colormap::Viridis => &[
+            0x440154, 0x472d7b, 0x3b528b, 0x2c728e, 0x21908c, 0x27ad81, 0x5dc863, 0xaadc32,
+            0xfde725,
+        ],
+        Colormap::Cividis => &[0x00204d, 0x414d6b, 0x7c7b78, 0xbcaf6f, 0xffea46],
+        Colormap::BlueRed => &[
+            0x2166ac, 0x67a9cf, 0xd1e5f0, 0xf7f7f7, 0xfddbc7, 0xef8a62, 0xb2182b,
+        ],
+    };
+    stops
+        .iter()
+        .map(|stop| [16, 8, 0].map(|shift| ((stop >> shift) & 0xff) as f32 / 255.))
+        .collect()
+}
+
+/// Samples `colormap` like [`bake_gradient`] samples two colors. The stops are close enough
+/// to blend in sRGB.
+pub fn bake_colormap(colormap: Colormap) -> Vec<[f32; 4]> {
+    let stops = colormap_stops(colormap);
+    let segments = (stops.len() - 1) as f32;
+    let positions: Vec<f32> = (0..stops.len()).map(|i| i as f32 / segments).collect();
+    bake_stops(&positions, &stops)
+}
+
+/// Samples the sRGB `colors` at `positions`, which rise from 0 to 1, like
+/// [`bake_gradient`] samples two colors, blending neighboring stops in sRGB. Two stops at the
+/// same position make a sharp edge.
+fn bake_stops(positions: &[f32], colors: &[[f32; 3]]) -> Vec<[f32; 4]> {
+    (0..GRADIENT_SIZE)
+        .map(|i| {
+            let t = i as f32 / (GRADIENT_SIZE - 1) as f32;
+            // the stops around `t`, the nearest one twice past the ends
+            let next = positions.partition_point(|&position| position <= t);
+            let (from, to) = (next.saturating_sub(1), next.min(positions.len() - 1));
+            let span = positions[to] - positions[from];
+            let f = if span > 0. {
+                (t - positions[from]) / span
+            } else {
+                0.
+            };
+            let (a, b) = (colors[from], colors[to]);
+            [
+                a[0] + (b[0] - a[0]) * f,
+                a[1] + (b[1] - a[1]) * f,
+                a[2] + (b[2] - a[2]) * f,
+                1.,
+            ]
+        })
+        .collect()
+}
+
+/// Most stops a [`GradientStops`] holds, enough for the 256 colors of an indexed palette.
+pub const MAX_STOPS: usize = 256;
+
+/// A gradient through sRGB colors at positions rising from 0 to 1, imported from a file.
+/// Fixed in size, so the [`crate::uniforms::UniformData`] holding it stays `Copy`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct GradientStops {
+    len: usize,
+    positions: [f32; MAX_STOPS],
+    colors: [[f32; 3]; MAX_STOPS],
+}
+
+impl GradientStops {
+    /// Collects `stops` of a position and a color, which need to be between 1 and
+    /// [`MAX_STOPS`], with positions rising in `0..=1`.
+    pub fn new(stops: impl IntoIterator<Item = (f32, [f32; 3])>) -> Result<Self, String> {
+        let mut gradient = Self {
+            len: 0,
+            positions: [0.; MAX_STOPS],
+            colors: [[0.; 3]; MAX_STOPS],
+        };
+        for (position, color) in stops {
+            if gradient.len == MAX_STOPS {
+                return Err(format!("Gradients can't have more than {MAX_STOPS} stops"));
+            }
+            let previous = gradient.positions[..gradient.len].last().copied();
+            if !(previous.unwrap_or(0.)..=1.).contains(&position) {
+                return Err(format!("The stop at {position} is out of order"));
+            }
+            gradient.positions[gradient.len] = position;
+            gradient.colors[gradient.len] = color;
+            gradient.len += 1;
+        }
+        if gradient.len == 0 {
+            return Err("The gradient doesn't have any stops".to_string());
+        }
+        Ok(gradient)
+    }
+
+    /// The positions and colors of the stops.
+    pub fn stops(&self) -> impl Iterator<Item = (f32, [f32; 3])> + '_ {
+        self.positions[..self.len]
+            .iter()
+            .copied()
+            .zip(self.colors[..self.len].iter().copied())
+    }
+
+    /// Samples the stops like [`bake_colormap`] does.
+    pub fn bake(&self) -> Vec<[f32; 4]> {
+        bake_stops(&self.positions[..self.len], &self.colors[..self.len])
+    }
+}
+
+impl std::fmt::Debug for GradientStops {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.stops()).finish()
+    }
+}
+
+impl Serialize for GradientStops {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.stops())
+    }
+}
+
+impl<'de> Deserialize<'de> for GradientStops {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stops = Vec::<(f32, [f32; 3])>::deserialize(deserializer)?;
+        Self::new(stops).map_err(D::Error::custom)
+    }
+}
+
+/// Converts an sRGB color with components in `0..=1` to the HSV values the shader
+/// interpolates, which are taken as sRGB too.
+fn rgb_to_hsva(r: f32, g: f32, b: f32) -> Hsva {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let h = if delta == 0. {
+        0.
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.) / 6.
+    } else if max == g {
+        ((b - r) / delta + 2.) / 6.
+    } else {
+        ((r - g) / delta + 4.) / 6.
+    };
+    let s = if max == 0. { 0. } else { delta / max };
+    Hsva::new(h, s, max, 1.)
+}
+
+/// Parses `count` numbers from `fields`, reporting them as coming from `line`.
+fn parse_numbers<'a>(
+    fields: &mut impl Iterator<Item = &'a str>,
+    count: usize,
+    line: usize,
+) -> Result<Vec<f32>, String> {
+    (0..count)
+        .map(|_| {
+            let field = fields
+                .next()
+                .ok_or_else(|| format!("Line {line} has too few values"))?;
+            field
+                .parse()
+                .map_err(|_| format!("Line {line}: {field:?} isn't a number"))
+        })
+        .collect()
+}
+
+/// Parses a GIMP palette (`.gpl`) or gradient (`.ggr`) file into a palette with all of its
+/// colors as [`GradientStops`], spread evenly for a palette. The segments of a gradient are
+/// taken as blended linearly, with their middle halfway between their colors.
+/// `fallback_name` is used when the file doesn't name the palette.
+pub fn parse_gimp(contents: &str, fallback_name: &str) -> Result<Palette, String> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()));
+    let gradient = match lines.next() {
+        Some((_, "GIMP Palette")) => false,
+        Some((_, "GIMP Gradient")) => true,
+        _ => return Err("Not a GIMP palette or gradient file".to_string()),
+    };
+
+    let mut name = fallback_name.to_string();
+    let mut colors = Vec::new();
+    let mut stops: Vec<(f32, [f32; 3])> = Vec::new();
+    let mut segment_count = None;
+    let mut segments = 0;
+    for (number, line) in lines {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = value.trim().to_string();
+            continue;
+        }
+        if !gradient && line.starts_with("Columns:") {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        if !gradient {
+            let rgb = parse_numbers(&mut fields, 3, number)?;
+            colors.push([rgb[0] / 255., rgb[1] / 255., rgb[2] / 255.]);
+        } else if segment_count.is_none() {
+            segment_count = Some(
+                line.parse::<usize>()
+                    .map_err(|_| format!("Line {number}: expected the number of segments"))?,
+            );
+        } else {
+            // left, middle and right position, then the left and right colors as RGBA
+            let values = parse_numbers(&mut fields, 11, number)?;
+            let [left, middle, right] = [values[0], values[1], values[2]];
+            let previous = stops.last().map_or(0., |&(position, _)| position);
+            if !(previous <= left && left <= middle && middle <= right && right <= 1.) {
+                return Err(format!("Line {number}: the segment is out of order"));
+            }
+            let (left_color, right_color) = (
+                [values[3], values[4], values[5]],
+                [values[7], values[8], values[9]],
+            );
+            let middle_color = [0, 1, 2].map(|c| (left_color[c] + right_color[c]) / 2.);
+            for stop in [
+                (left, left_color),
+                (middle, middle_color),
+                (right, right_color),
+            ] {
+                // neighboring segments usually meet in the same color
+                if stops.last() != Some(&stop) {
+                    stops.push(stop);
+                }
+            }
+            segments += 1;
+        }
+    }
+
+    if gradient {
+        if let Some(count) = segment_count.filter(|&count| count != segments) {
+            return Err(format!(
+                "The file should have {count} segments but has {segments}"
+            ));
+        }
+    } else {
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        stops = (colors.iter().enumerate())
+            .map(|(i, &color)| (i as f32 / last, color))
+            .collect();
+    }
+    let color = |stop: Option<&(f32, [f32; 3])>| stop.map(|&(_, [r, g, b])| rgb_to_hsva(r, g, b));
+    match (color(stops.first()), color(stops.last())) {
+        (Some(start_color), Some(end_color)) => Ok(Palette {
+            name,
+            start_color,
+            end_color,
+            colormap: None,
+            stops: Some(GradientStops::new(stops)?),
+        }),
+        _ => Err("The file doesn't contain any colors".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colormaps_end_at_their_stops() {
+        for colormap in Colormap::ALL {
+            let stops = colormap_stops(colormap);
+            let baked = bake_colormap(colormap);
+            assert_eq!(baked.len(), GRADIENT_SIZE);
+            for (color, stop) in [
+                (baked[0], stops[0]),
+                (baked[GRADIENT_SIZE - 1], stops[stops.len() - 1]),
+            ] {
+                for c in 0..3 {
+                    assert!(
+                        (color[c] - stop[c]).abs() < 1e-5,
+                        "{colormap:?}: {color:?} != {stop:?}"
+                    );
+                }
+                assert_eq!(color[3], 1.);
+            }
+        }
+    }
+
+    #[test]
+    fn colormap_segments_are_monotonic() {
+        for colormap in Colormap::ALL {
+            let stops = colormap_stops(colormap);
+            let baked = bake_colormap(colormap);
+            let segments = stops.len() - 1;
+            let segment = |i: usize| {
+                ((i as f32 / (GRADIENT_SIZE - 1) as f32 * segments as f32) as usize)
+                    .min(segments - 1)
+            };
+            for i in 0..GRADIENT_SIZE - 1 {
+                // within a segment, each channel only moves towards the segment's next stop
+                if segment(i) != segment(i + 1) {
+                    continue;
+                }
+                let (from, to) = (stops[segment(i)], stops[segment(i) + 1]);
+                for c in 0..3 {
+                    let step = (baked[i + 1][c] - baked[i][c]) * (to[c] - from[c]).signum();
+                    assert!(step >= -1e-6, "{colormap:?} turns back at {i}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gimp_palettes_keep_all_colors() {
+        let contents = "GIMP Palette\nName: Flag\nColumns: 3\n#\n255 0 0 Red\n\
+                        255 255 255\tWhite\n  0 0 255 Blue\n";
+        let palette = parse_gimp(contents, "file").unwrap();
+        assert_eq!(palette.name, "Flag");
+        let stops: Vec<_> = palette.stops.unwrap().stops().collect();
+        assert_eq!(
+            stops,
+            [(0., [1., 0., 0.]), (0.5, [1., 1., 1.]), (1., [0., 0., 1.])]
+        );
+        assert_eq!(palette.start_color, rgb_to_hsva(1., 0., 0.));
+        assert_eq!(palette.end_color, rgb_to_hsva(0., 0., 1.));
+        let baked = palette.stops.unwrap().bake();
+        assert!(baked[GRADIENT_SIZE / 2][1] > 0.99);
+    }
+
+    #[test]
+    fn gimp_gradients_keep_all_segments() {
+        let contents = "GIMP Gradient\nName: Sunset\n2\n\
+                        0.0 0.25 0.5 0 0 0 1 1 0 0 1 0 0\n\
+                        0.5 0.75 1.0 1 0 0 1 1 1 0 1 0 0 0 0\n";
+        let palette = parse_gimp(contents, "file").unwrap();
+        assert_eq!(palette.name, "Sunset");
+        let stops: Vec<_> = palette.stops.unwrap().stops().collect();
+        assert_eq!(
+            stops,
+            [
+                (0., [0., 0., 0.]),
+                (0.25, [0.5, 0., 0.]),
+                (0.5, [1., 0., 0.]),
+                (0.75, [1., 0.5, 0.]),
+                (1., [1., 1., 0.]),
+            ]
+        );
+        let baked = palette.stops.unwrap().bake();
+        assert_eq!(baked[0], [0., 0., 0., 1.]);
+        assert_eq!(baked[GRADIENT_SIZE - 1], [1., 1., 0., 1.]);
+    }
+
+    #[test]
+    fn malformed_gimp_files_are_errors() {
+        for contents in [
+            "",
+            "GIMP Palette\n",
+            "GIMP Palette\n255 0\n",
+            "GIMP Palette\n255 x 0\n",
+            "GIMP Swatches\n255 0 0\n",
+            "GIMP Gradient\n1\n0.0 0.5 1.0 0 0 0 1 1 1\n",
+            "GIMP Gradient\ntwo\n",
+            "GIMP Gradient\n2\n0.0 0.5 1.0 0 0 0 1 1 1 1 1 0 0\n",
+            "GIMP Gradient\n1\n0.0 0.8 0.5 0 0 0 1 1 1 1 1 0 0\n",
+        ] {
+            assert!(parse_gimp(contents, "file").is_err(), "{contents:?}");
+        }
+        let too_many = format!("GIMP Palette\n{}", "1 2 3\n".repeat(MAX_STOPS + 1));
+        assert!(parse_gimp(&too_many, "file").is_err());
+    }
+
+    // JSON only comes with the app
+    #[cfg(feature = "gui")]
+    #[test]
+    fn gradient_stops_round_trip() {
+        let stops = GradientStops::new([(0., [0., 0.5, 1.]), (1., [1., 0.5, 0.])]).unwrap();
+        let json = serde_json::to_string(&stops).unwrap();
+        assert_eq!(serde_json::from_str::<GradientStops>(&json).unwrap(), stops);
+        assert!(serde_json::from_str::<GradientStops>("[[0.5,[0,0,0]],[0.2,[0,0,0]]]").is_err());
+    }
+}
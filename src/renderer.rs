@@ -1,101 +1,590 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    thread::{self, ThreadId},
+};
+
 use glow::HasContext;
 
-use crate::app::UniformData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fixed::Fixed,
+    orbit::reference_orbit,
+    palette::{bake_colormap, bake_gradient, GradientStops, GRADIENT_SIZE},
+    uniforms::{Colormap, Interpolation, OutputLayer, UniformData},
+};
+
+const SHADER_VERSION: &str = if cfg!(target_arch = "wasm32") {
+    "#version 300 es"
+} else {
+    "#version 330"
+};
+
+const VERTEX_SHADER: &str = r#"
+    const vec2 verts[6] = vec2[6](
+        vec2(-1.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(1.0, -1.0),
+        vec2(-1.0, -1.0),
+        vec2(-1.0, 1.0),
+        vec2(1.0, 1.0)
+    );
+    out vec4 v_color;
+    void main() {
+        gl_Position = vec4(verts[gl_VertexID], 0.0, 1.0);
+    }
+"#;
+
+pub const MANDELBROT_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    if (iteration_index == 0) {
+        // the orbit starts at z_seed, previous_z is the iterate after it
+        previous_z = complex_mul(z_seed, z_seed) + original_z;
+    }
+    vec2 z;
+    z.x = previous_z.x * previous_z.x - previous_z.y * previous_z.y + original_z.x;
+    z.y = 2. * previous_z.x * previous_z.y + original_z.y;
+
+    return z;
+}
+"#;
+
+/// Newton's method for `z^newton_degree - 1`, meant to be rendered with `convergent` set.
+pub const NEWTON_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    vec2 f = complex_pow(previous_z, newton_degree) - vec2(1., 0.);
+    vec2 df = float(newton_degree) * complex_pow(previous_z, newton_degree - 1);
+
+    return previous_z - complex_div(f, df);
+}
+"#;
+
+pub const PHOENIX_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    if (iteration_index == 0) {
+        // the orbit starts at z_seed, previous_z is the iterate after it
+        older_z = z_seed;
+        previous_z = complex_mul(z_seed, z_seed) + original_z;
+    }
+    return complex_mul(previous_z, previous_z) + original_z + phoenix_p * older_z;
+}
+"#;
+
+pub const BURNING_SHIP_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    vec2 z = abs(previous_z);
+    return complex_mul(z, z) + original_z;
+}
+"#;
+
+pub const TRICORN_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    vec2 conjugate = vec2(previous_z.x, -previous_z.y);
+    return complex_mul(conjugate, conjugate) + original_z;
+}
+"#;
+
+pub const CELTIC_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    vec2 z = complex_mul(previous_z, previous_z);
+    return vec2(abs(z.x), z.y) + original_z;
+}
+"#;
+
+pub const MULTIBROT_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    return complex_pow(previous_z, 4) + original_z;
+}
+"#;
+
+pub const MAGNET_FUNC: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    vec2 numerator = complex_mul(previous_z, previous_z) + original_z - vec2(1., 0.);
+    vec2 denominator = 2. * previous_z + original_z - vec2(2., 0.);
+    vec2 z = complex_div(numerator, denominator);
+    return complex_mul(z, z);
+}
+"#;
+
+/// Mandelbrot and Tricorn steps taking turns, using the iteration index.
+pub const ALTERNATING_FUNC: &str = r#"
+// Besides the arguments, these globals can be read:
+//   int iteration_index: the current iteration, starting at 0
+//   vec2 screen_position: the pixel in the view, (0, 0) top left, (1, 1) bottom right
+//   vec2 older_z: the iterate before previous_z
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    vec2 z = previous_z;
+    if (iteration_index % 2 == 1) {
+        z.y = -z.y;
+    }
+    return complex_mul(z, z) + original_z;
+}
+"#;
+
+/// Julia set of a constant in between `constant_a` and `constant_b`, picked by `blend`.
+pub const BLENDED_JULIA_FUNC: &str = r#"
+// constant_a, constant_b and blend are set in the app, use screen_position.x
+// instead of blend to morph from one Julia set to the other across the view
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    return complex_mul(previous_z, previous_z) + mix(constant_a, constant_b, blend);
+}
+"#;
+
+/// Starting points for custom fractal functions, as `(name, function)`.
+/// Besides their two arguments, functions can read the `iteration_index`, `screen_position`
+/// and `older_z` globals of `frag.glsl`, see [`ALTERNATING_FUNC`], the `constant_a`,
+/// `constant_b` and `blend` uniforms, see [`BLENDED_JULIA_FUNC`], and the `z_seed` uniform,
+/// see [`MANDELBROT_FUNC`].
+pub const EXAMPLE_FUNCS: [(&str, &str); 8] = [
+    ("Mandelbrot", MANDELBROT_FUNC),
+    ("Burning Ship", BURNING_SHIP_FUNC),
+    ("Tricorn", TRICORN_FUNC),
+    ("Celtic", CELTIC_FUNC),
+    ("Multibrot (z^4)", MULTIBROT_FUNC),
+    ("Magnet", MAGNET_FUNC),
+    ("Alternating Tricorn", ALTERNATING_FUNC),
+    ("Blended Julia", BLENDED_JULIA_FUNC),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FractalType {
+    #[default]
+    Mandelbrot,
+    Newton,
+    Phoenix,
+    Custom,
+}
 
+impl FractalType {
+    pub const ALL: [FractalType; 4] = [
+        FractalType::Mandelbrot,
+        FractalType::Newton,
+        FractalType::Phoenix,
+        FractalType::Custom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FractalType::Mandelbrot => "Mandelbrot",
+            FractalType::Newton => "Newton",
+            FractalType::Phoenix => "Phoenix",
+            FractalType::Custom => "Custom",
+        }
+    }
+
+    /// The builtin `iteration` function, `None` for [`FractalType::Custom`].
+    pub fn function(self) -> Option<&'static str> {
+        match self {
+            FractalType::Mandelbrot => Some(MANDELBROT_FUNC),
+            FractalType::Newton => Some(NEWTON_FUNC),
+            FractalType::Phoenix => Some(PHOENIX_FUNC),
+            FractalType::Custom => None,
+        }
+    }
+
+    /// Region of the plane with the interesting part of the fractal, what the "Whole fractal"
+    /// button shows. Custom functions get the square most Julia sets fit in.
+    pub fn framing(self) -> egui::Rect {
+        match self {
+            FractalType::Mandelbrot => egui::Rect::from_x_y_ranges(-2.5..=1., -1.25..=1.25),
+            FractalType::Newton | FractalType::Phoenix | FractalType::Custom => {
+                egui::Rect::from_x_y_ranges(-2.0..=2., -2.0..=2.)
+            }
+        }
+    }
+}
+
+/// Width of the texture holding the reference orbit, must match `REFERENCE_WIDTH` in
+/// `frag.glsl`. Longer orbits continue on the next row.
+const REFERENCE_WIDTH: usize = 1024;
+
+/// Why a fractal function couldn't be turned into a program. Displays as the log, so it can
+/// be shown like the plain compiler log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderError {
+    /// Log of the compiler or linker, or why the function was rejected before compiling.
+    pub log: String,
+    /// Line of the fractal function with the first error, counting from 1. `None` when the
+    /// log doesn't point into the function, like for link errors.
+    pub line: Option<u32>,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.log)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<String> for ShaderError {
+    fn from(log: String) -> Self {
+        Self { log, line: None }
+    }
+}
+
+/// Number of rows drawn at once by the `render_to_buffer` functions, between progress updates.
+pub const RENDER_STRIP_HEIGHT: u32 = 256;
+
+/// Start and end colors as HSV values, how they're blended and the colormap or imported
+/// stops replacing them.
+type GradientKey = (
+    [f32; 6],
+    Interpolation,
+    Option<Colormap>,
+    Option<GradientStops>,
+);
+
+/// Owns the GL objects used to draw the fractal.
+///
+/// The objects are plain ids, so the struct is `Send` and can sit in the `Arc<Mutex<_>>`
+/// shared with egui's paint callbacks, but they only mean something to the context they were
+/// created in. Every method taking a `gl` has to be called on the thread that created the
+/// renderer, which for the app is the UI thread, debug builds check this.
 pub struct Renderer {
     program: glow::Program,
+    /// Programs of the builtin fractal types, compiled the first time they are used.
+    cache: HashMap<FractalType, glow::Program>,
+    /// Whether `program` is owned by `cache`, rather than compiled from a custom function.
+    cached: bool,
+    /// Created once and bound again by every [`Self::paint`], so it doesn't depend on the
+    /// program and survives program swaps. It holds no attributes, the vertex shader makes
+    /// the quad from `gl_VertexID`, but core profiles can't draw without one bound.
     vertex_array: glow::VertexArray,
+    /// Thread owning the GL context.
+    thread: ThreadId,
+    /// Largest width and height of a texture the driver supports.
+    max_texture_size: u32,
+    /// Iterates of the reference orbit as `RG32F` texels, read by `frag.glsl` when
+    /// [`UniformData::perturbation`] is set.
+    reference_orbit: glow::Texture,
+    /// Number of iterates in `reference_orbit`.
+    reference_length: i32,
+    /// `origin` and `cycles` the reference orbit was computed for.
+    reference_key: Option<([Fixed; 2], i32)>,
+    /// Whether the driver passed [`probe_double_float`].
+    double_float_supported: bool,
+    /// The palette baked by [`bake_gradient`] or [`bake_colormap`], one row of `RGBA16F`
+    /// texels.
+    gradient: glow::Texture,
+    /// HSV values of the start and end colors in `gradient`, how they were blended and the
+    /// colormap replacing them, it's baked again by [`Self::paint`] when they change.
+    gradient_key: Cell<Option<GradientKey>>,
+    /// Square textures and their framebuffers [`Self::render_preview`] and
+    /// [`Self::render_preview_iterations`] render into, by internal format, with their side.
+    /// Kept between previews and made again when a larger one is asked for.
+    preview_targets: RefCell<HashMap<u32, (glow::Texture, glow::Framebuffer, u32)>>,
 }
 
 impl Renderer {
-    pub fn new(gl: &glow::Context) -> Self {
+    /// Fails when the driver can't compile the builtin shader, most likely because it doesn't
+    /// support the GLSL version the shaders are written in.
+    pub fn new(gl: &glow::Context) -> Result<Self, String> {
         use glow::HasContext as _;
 
-        let shader_version = if cfg!(target_arch = "wasm32") {
-            "#version 300 es"
-        } else {
-            "#version 330"
-        };
-
         unsafe {
-            let program = gl.create_program().expect("Cannot create program");
-
-            let (vertex_shader_source, fragment_shader_source) = (
-                // don't touch this
-                r#"
-                    const vec2 verts[6] = vec2[6](
-                        vec2(-1.0, -1.0),
-                        vec2(1.0, 1.0),
-                        vec2(1.0, -1.0),
-                        vec2(-1.0, -1.0),
-                        vec2(-1.0, 1.0),
-                        vec2(1.0, 1.0)
-                    );
-                    out vec4 v_color;
-                    void main() {
-                        gl_Position = vec4(verts[gl_VertexID], 0.0, 1.0);
-                    }
-                "#,
-                include_str!("frag.glsl"),
-            );
-
-            let shader_sources = [
-                (glow::VERTEX_SHADER, vertex_shader_source),
-                (glow::FRAGMENT_SHADER, fragment_shader_source),
-            ];
-
-            let shaders: Vec<_> = shader_sources
-                .iter()
-                .map(|(shader_type, shader_source)| {
-                    let shader = gl
-                        .create_shader(*shader_type)
-                        .expect("Cannot create shader");
-                    gl.shader_source(shader, &format!("{}\n{}", shader_version, shader_source));
-                    gl.compile_shader(shader);
-                    assert!(
-                        gl.get_shader_compile_status(shader),
-                        "Failed to compile {shader_type}: {}",
-                        gl.get_shader_info_log(shader)
-                    );
-                    gl.attach_shader(program, shader);
-                    shader
-                })
-                .collect();
-
-            gl.link_program(program);
-            assert!(
-                gl.get_program_link_status(program),
-                "{}",
-                gl.get_program_info_log(program)
+            let program = create_program(gl, MANDELBROT_FUNC).map_err(|error| {
+                format!(
+                    "The graphics driver can't compile the fractal shader. It needs {}, \
+                     this driver has {}.\n\n{error}",
+                    if cfg!(target_arch = "wasm32") {
+                        "WebGL 2"
+                    } else {
+                        "OpenGL 3.3"
+                    },
+                    gl.get_parameter_string(glow::VERSION)
+                )
+            })?;
+
+            let vertex_array = gl.create_vertex_array()?;
+            let double_float_supported = probe_double_float(gl, vertex_array);
+
+            let reference_orbit = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(reference_orbit));
+            // float textures can't be filtered on WebGL 2, the shader reads single texels anyway
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
             );
 
-            for shader in shaders {
-                gl.detach_shader(program, shader);
-                gl.delete_shader(shader);
+            let gradient = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(gradient));
+            // 16-bit float textures can be filtered everywhere, so neighboring colors blend
+            for (parameter, value) in [
+                (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+                (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+                (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+                (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+            ] {
+                gl.tex_parameter_i32(glow::TEXTURE_2D, parameter, value as i32);
             }
+            gl.bind_texture(glow::TEXTURE_2D, None);
 
-            let vertex_array = gl
-                .create_vertex_array()
-                .expect("Cannot create vertex array");
-
-            Self {
+            Ok(Self {
                 program,
+                cache: HashMap::from([(FractalType::Mandelbrot, program)]),
+                cached: true,
                 vertex_array,
+                thread: thread::current().id(),
+                max_texture_size: gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) as u32,
+                reference_orbit,
+                reference_length: 0,
+                reference_key: None,
+                double_float_supported,
+                gradient,
+                gradient_key: Cell::new(None),
+                preview_targets: RefCell::new(HashMap::new()),
+            })
+        }
+    }
+
+    /// Largest width or height the `render_to_buffer` functions can render at once.
+    pub fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
+    }
+
+    /// Whether [`UniformData::double_float`] works on this driver. Where it doesn't, the
+    /// pairs of floats lose their extra precision and the view turns into noise.
+    pub fn double_float_supported(&self) -> bool {
+        self.double_float_supported
+    }
+
+    /// Panics in debug builds if called off the thread owning the GL context.
+    fn check_thread(&self) {
+        debug_assert_eq!(
+            thread::current().id(),
+            self.thread,
+            "The renderer has to be used on the thread owning its GL context"
+        );
+    }
+
+    /// Switches to the program of `fractal_type`. Builtin types are compiled once and reused,
+    /// `Custom` compiles `custom_fractal_function` every time.
+    pub fn set_fractal_type(
+        &mut self,
+        gl: &glow::Context,
+        fractal_type: FractalType,
+        custom_fractal_function: &str,
+    ) -> Result<(), ShaderError> {
+        self.check_thread();
+        let Some(function) = fractal_type.function() else {
+            return self.set_fractal_function(gl, custom_fractal_function);
+        };
+
+        let program = match self.cache.get(&fractal_type) {
+            Some(&program) => program,
+            None => {
+                let program = unsafe { create_program(gl, function)? };
+                self.cache.insert(fractal_type, program);
+                program
             }
+        };
+        self.replace_program(gl, program, true);
+        Ok(())
+    }
+
+    /// Replaces the current program with one using `fractal_function` as the
+    /// `iteration` function. On error, the old program is kept and the compiler log is returned.
+    pub fn set_fractal_function(
+        &mut self,
+        gl: &glow::Context,
+        fractal_function: &str,
+    ) -> Result<(), ShaderError> {
+        self.check_thread();
+        let program = unsafe { create_program(gl, fractal_function)? };
+        self.replace_program(gl, program, false);
+        Ok(())
+    }
+
+    /// Makes `program` current, deleting the old one unless it belongs to the cache.
+    fn replace_program(&mut self, gl: &glow::Context, program: glow::Program, cached: bool) {
+        if !self.cached {
+            unsafe { gl.delete_program(self.program) };
         }
+        self.program = program;
+        self.cached = cached;
+    }
+
+    /// Checks that `fractal_function` compiles and links, without replacing the current program.
+    pub fn validate_fractal_function(
+        &self,
+        gl: &glow::Context,
+        fractal_function: &str,
+    ) -> Result<(), ShaderError> {
+        self.check_thread();
+        unsafe {
+            let program = create_program(gl, fractal_function)?;
+            gl.delete_program(program);
+        }
+        Ok(())
     }
 
     pub fn destroy(&self, gl: &glow::Context) {
         use glow::HasContext as _;
+
+        self.check_thread();
         unsafe {
-            gl.delete_program(self.program);
+            if !self.cached {
+                gl.delete_program(self.program);
+            }
+            for &program in self.cache.values() {
+                gl.delete_program(program);
+            }
             gl.delete_vertex_array(self.vertex_array);
+            gl.delete_texture(self.reference_orbit);
+            gl.delete_texture(self.gradient);
+            for (_, (texture, framebuffer, _)) in self.preview_targets.take() {
+                gl.delete_framebuffer(framebuffer);
+                gl.delete_texture(texture);
+            }
+        }
+    }
+
+    /// Bakes the palette of `uniform_data` into `gradient`, unless it's already there.
+    fn update_gradient(&self, gl: &glow::Context, uniform_data: &UniformData) {
+        let (start, end) = (uniform_data.start_color, uniform_data.end_color);
+        let key = (
+            [start.h, start.s, start.v, end.h, end.s, end.v],
+            uniform_data.interpolation,
+            uniform_data.colormap,
+            uniform_data.gradient_stops,
+        );
+        if self.gradient_key.get() == Some(key) {
+            return;
+        }
+        let gradient = match (uniform_data.gradient_stops, uniform_data.colormap) {
+            (Some(stops), _) => stops.bake(),
+            (None, Some(colormap)) => bake_colormap(colormap),
+            (None, None) => bake_gradient(start, end, uniform_data.interpolation),
+        };
+        let texels: Vec<u8> = gradient
+            .iter()
+            .flatten()
+            .flat_map(|c| c.to_ne_bytes())
+            .collect();
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.gradient));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA16F as i32,
+                GRADIENT_SIZE as i32,
+                1,
+                0,
+                glow::RGBA,
+                glow::FLOAT,
+                Some(&texels),
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
+        self.gradient_key.set(Some(key));
+    }
+
+    /// Computes the reference orbit at `uniform_data.origin` and uploads it, unless it's
+    /// already there. Has to be called before painting with [`UniformData::perturbation`] set.
+    pub fn update_reference_orbit(&mut self, gl: &glow::Context, uniform_data: &UniformData) {
+        self.check_thread();
+        let key = (uniform_data.origin, uniform_data.cycles);
+        if self.reference_key == Some(key) {
+            return;
+        }
+        let orbit = reference_orbit(uniform_data.origin, uniform_data.cycles);
+        let rows = orbit.len().div_ceil(REFERENCE_WIDTH);
+        let mut texels = vec![0u8; REFERENCE_WIDTH * rows * 8];
+        for (texel, z) in texels.chunks_exact_mut(8).zip(&orbit) {
+            texel[..4].copy_from_slice(&z.x.to_ne_bytes());
+            texel[4..].copy_from_slice(&z.y.to_ne_bytes());
+        }
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.reference_orbit));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RG32F as i32,
+                REFERENCE_WIDTH as i32,
+                rows as i32,
+                0,
+                glow::RG,
+                glow::FLOAT,
+                Some(&texels),
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
         }
+        self.reference_length = orbit.len() as i32;
+        self.reference_key = Some(key);
+    }
+
+    /// Whether the last reference orbit escaped before its `cycles`, so the points that stay
+    /// in longer start over from its beginning.
+    pub fn reference_escaped(&self) -> bool {
+        // the orbit holds zero and then `cycles + 1` iterates, see `reference_orbit`
+        self.reference_key
+            .is_some_and(|(_, cycles)| self.reference_length < cycles + 2)
     }
 
+    /// Fills the scissor box, or the whole framebuffer without a scissor test,
+    /// with the background color.
+    pub fn clear(&self, gl: &glow::Context, uniform_data: UniformData) {
+        self.check_thread();
+        let [r, g, b, a] =
+            egui::Color32::from(uniform_data.background_color).to_normalized_gamma_f32();
+        unsafe {
+            gl.clear_color(r, g, b, a);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+        }
+    }
+
+    /// Draws into `framebuffer`, `None` for the default one, over `viewport` given as
+    /// `[x, y, width, height]` in pixels from the bottom left corner. The view fills the
+    /// viewport, whatever `resolution` and `window_offset` of `uniform_data` say. Leaves
+    /// `framebuffer` bound and the viewport set, so hosts drawing more have to restore theirs.
+    pub fn paint_into(
+        &self,
+        gl: &glow::Context,
+        framebuffer: Option<glow::Framebuffer>,
+        viewport: [i32; 4],
+        uniform_data: UniformData,
+    ) {
+        self.check_thread();
+        let [x, y, width, height] = viewport;
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, framebuffer);
+            gl.viewport(x, y, width, height);
+        }
+        self.paint(
+            gl,
+            UniformData {
+                resolution: egui::vec2(width as f32, height as f32),
+                window_offset: egui::vec2(x as f32, y as f32),
+                ..uniform_data
+            },
+        );
+    }
+
+    /// Draws into the framebuffer and viewport that are bound, which is how egui's paint
+    /// callbacks use it. `resolution` and `window_offset` of `uniform_data` have to place the
+    /// view in the framebuffer, [`Self::paint_into`] sets them from a viewport.
     pub fn paint(&self, gl: &glow::Context, uniform_data: UniformData) {
+        self.check_thread();
+        self.update_gradient(gl, &uniform_data);
         unsafe {
             gl.use_program(Some(self.program));
+            // unit 0 is left to egui and the reference orbit
+            gl.active_texture(glow::TEXTURE1);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.gradient));
+            gl.active_texture(glow::TEXTURE0);
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "gradient").as_ref(),
+                1,
+            );
             gl.uniform_2_f32(
                 gl.get_uniform_location(self.program, "center").as_ref(),
                 uniform_data.center.x,
@@ -105,6 +594,149 @@ impl Renderer {
                 gl.get_uniform_location(self.program, "cycles").as_ref(),
                 uniform_data.cycles,
             );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "convergent").as_ref(),
+                uniform_data.convergent as i32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "period_checking")
+                    .as_ref(),
+                uniform_data.period_checking as i32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "perturbation")
+                    .as_ref(),
+                uniform_data.perturbation as i32,
+            );
+            if uniform_data.perturbation {
+                gl.active_texture(glow::TEXTURE0);
+                gl.bind_texture(glow::TEXTURE_2D, Some(self.reference_orbit));
+                gl.uniform_1_i32(
+                    gl.get_uniform_location(self.program, "reference_orbit")
+                        .as_ref(),
+                    0,
+                );
+                gl.uniform_1_i32(
+                    gl.get_uniform_location(self.program, "reference_length")
+                        .as_ref(),
+                    self.reference_length,
+                );
+            }
+            gl.uniform_1_f32(gl.get_uniform_location(self.program, "df_one").as_ref(), 1.);
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "double_float")
+                    .as_ref(),
+                uniform_data.double_float as i32,
+            );
+            // the rounded coordinates and what they lost
+            let [x, y] = uniform_data.origin.map(Fixed::to_f64);
+            let (rounded_x, rounded_y) = (x as f32, y as f32);
+            gl.uniform_4_f32(
+                gl.get_uniform_location(self.program, "origin").as_ref(),
+                rounded_x,
+                rounded_y,
+                (x - rounded_x as f64) as f32,
+                (y - rounded_y as f64) as f32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "newton_degree")
+                    .as_ref(),
+                uniform_data.newton_degree,
+            );
+            gl.uniform_1_f32(
+                gl.get_uniform_location(self.program, "phoenix_p").as_ref(),
+                uniform_data.phoenix_p,
+            );
+            let [constant_a, constant_b] = uniform_data.constants;
+            gl.uniform_2_f32(
+                gl.get_uniform_location(self.program, "constant_a").as_ref(),
+                constant_a.x,
+                constant_a.y,
+            );
+            gl.uniform_2_f32(
+                gl.get_uniform_location(self.program, "constant_b").as_ref(),
+                constant_b.x,
+                constant_b.y,
+            );
+            gl.uniform_2_f32(
+                gl.get_uniform_location(self.program, "z_seed").as_ref(),
+                uniform_data.z_seed.x,
+                uniform_data.z_seed.y,
+            );
+            gl.uniform_1_f32(
+                gl.get_uniform_location(self.program, "blend").as_ref(),
+                uniform_data.blend,
+            );
+            gl.uniform_1_f32(
+                gl.get_uniform_location(self.program, "morph").as_ref(),
+                uniform_data.morph,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "coloring_mode")
+                    .as_ref(),
+                uniform_data.coloring_mode as i32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "color_bands")
+                    .as_ref(),
+                uniform_data.color_bands,
+            );
+            gl.uniform_1_f32(
+                gl.get_uniform_location(self.program, "color_offset")
+                    .as_ref(),
+                uniform_data.color_offset,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "cyclic_gradient")
+                    .as_ref(),
+                uniform_data.cyclic_gradient as i32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "smoothing").as_ref(),
+                uniform_data.smoothing as i32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "dither").as_ref(),
+                uniform_data.dither as i32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "antialiasing")
+                    .as_ref(),
+                uniform_data.antialiasing as i32,
+            );
+            gl.uniform_3_f32_slice(
+                gl.get_uniform_location(self.program, "brightness").as_ref(),
+                &uniform_data.brightness,
+            );
+            gl.uniform_3_f32_slice(
+                gl.get_uniform_location(self.program, "contrast").as_ref(),
+                &uniform_data.contrast,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "unclamped").as_ref(),
+                uniform_data.unclamped as i32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "transparent_interior")
+                    .as_ref(),
+                uniform_data.transparent_interior as i32,
+            );
+            let [color_min, color_max] = uniform_data.gradient_range();
+            gl.uniform_2_f32(
+                gl.get_uniform_location(self.program, "color_range")
+                    .as_ref(),
+                color_min as f32,
+                color_max as f32,
+            );
+            gl.uniform_1_i32(
+                gl.get_uniform_location(self.program, "output_layer")
+                    .as_ref(),
+                uniform_data.output_layer as i32,
+            );
+            gl.uniform_1_f32(
+                gl.get_uniform_location(self.program, "rotation").as_ref(),
+                uniform_data.rotation,
+            );
             gl.uniform_1_f32(
                 gl.get_uniform_location(self.program, "zoom").as_ref(),
                 uniform_data.zoom,
@@ -120,66 +752,392 @@ impl Renderer {
                 uniform_data.window_offset.x,
                 uniform_data.window_offset.y,
             );
-            gl.uniform_3_f32(
-                gl.get_uniform_location(self.program, "start_color")
-                    .as_ref(),
-                uniform_data.start_color.h,
-                uniform_data.start_color.s,
-                uniform_data.start_color.v,
-            );
-            gl.uniform_3_f32(
-                gl.get_uniform_location(self.program, "end_color").as_ref(),
-                uniform_data.end_color.h,
-                uniform_data.end_color.s,
-                uniform_data.end_color.v,
-            );
 
+            // egui binds its own vertex array between our paints, so this can't be done once
             gl.bind_vertex_array(Some(self.vertex_array));
             gl.draw_arrays(glow::TRIANGLES, 0, 6);
+            if uniform_data.perturbation {
+                gl.bind_texture(glow::TEXTURE_2D, None);
+            }
+            gl.active_texture(glow::TEXTURE1);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.active_texture(glow::TEXTURE0);
         }
     }
 
+    /// Renders the fractal into an RGBA buffer with 8 bits per channel, rows go from the top.
+    ///
+    /// The image is drawn in strips of [`RENDER_STRIP_HEIGHT`] rows and `progress` is called
+    /// after each one with the finished fraction in `0.0..=1.0`. Everything runs on the calling
+    /// thread, which has to own the GL context, so in the app this blocks the UI thread and
+    /// the callback can't rely on egui repainting in between.
+    ///
+    /// Fails when the driver can't provide a texture of this size and format.
     pub fn render_to_buffer(
         &self,
         gl: &glow::Context,
         width: u32,
         height: u32,
         uniform_data: UniformData,
-    ) -> Vec<u8> {
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<Vec<u8>, String> {
+        self.render_pixels(
+            gl,
+            width,
+            height,
+            uniform_data,
+            progress,
+            glow::RGBA8,
+            glow::UNSIGNED_BYTE,
+            1,
+        )
+    }
+
+    /// Like [`Self::render_to_buffer`], but with 16 bits per channel. WebGL can't render
+    /// into 16-bit textures, so this needs desktop GL.
+    pub fn render_to_buffer_16(
+        &self,
+        gl: &glow::Context,
+        width: u32,
+        height: u32,
+        uniform_data: UniformData,
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<Vec<u16>, String> {
+        // the dithering noise is sized for 8-bit channels
+        let uniform_data = UniformData {
+            dither: false,
+            ..uniform_data
+        };
+        let bytes = self.render_pixels(
+            gl,
+            width,
+            height,
+            uniform_data,
+            progress,
+            glow::RGBA16,
+            glow::UNSIGNED_SHORT,
+            2,
+        )?;
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+            .collect())
+    }
+
+    /// Like [`Self::render_to_buffer`], but into a floating point texture, so colors aren't
+    /// quantized before export and brightness and contrast can push them outside of `0..=1`.
+    /// Needs desktop GL like [`Self::render_to_buffer_16`].
+    pub fn render_to_buffer_hdr(
+        &self,
+        gl: &glow::Context,
+        width: u32,
+        height: u32,
+        uniform_data: UniformData,
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<Vec<f32>, String> {
+        let uniform_data = UniformData {
+            dither: false,
+            unclamped: true,
+            ..uniform_data
+        };
+        let bytes = self.render_pixels(
+            gl,
+            width,
+            height,
+            uniform_data,
+            progress,
+            glow::RGBA32F,
+            glow::FLOAT,
+            4,
+        )?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect())
+    }
+
+    /// Renders the iteration at which each pixel escaped or converged, `cycles` for the ones
+    /// that did neither, without any coloring. Needs desktop GL like [`Self::render_to_buffer_16`].
+    pub fn render_iterations(
+        &self,
+        gl: &glow::Context,
+        width: u32,
+        height: u32,
+        uniform_data: UniformData,
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<Vec<u16>, String> {
+        let uniform_data = UniformData {
+            output_layer: OutputLayer::Iterations,
+            ..uniform_data
+        };
+        // the count is in the red channel
+        let pixels = self.render_to_buffer_16(gl, width, height, uniform_data, progress)?;
+        Ok(pixels.into_iter().step_by(4).collect())
+    }
+
+    /// Renders the coverage of the set, 255 for points that neither escaped nor converged
+    /// and 0 for the rest.
+    pub fn render_set_mask(
+        &self,
+        gl: &glow::Context,
+        width: u32,
+        height: u32,
+        uniform_data: UniformData,
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<Vec<u8>, String> {
+        let uniform_data = UniformData {
+            output_layer: OutputLayer::SetMask,
+            ..uniform_data
+        };
+        // all channels hold the same value
+        let pixels = self.render_to_buffer(gl, width, height, uniform_data, progress)?;
+        Ok(pixels.into_iter().step_by(4).collect())
+    }
+
+    /// Renders `uniform_data` scaled down to fit in `max_dim` by `max_dim` pixels, keeping
+    /// the aspect ratio, and returns the size and the RGBA pixels (top row first). Views
+    /// smaller than that are rendered at their own resolution.
+    ///
+    /// Meant for small images drawn every now and then, like thumbnails. Unlike
+    /// [`Self::render_to_buffer`], it draws in one pass without reporting progress and reuses
+    /// the same texture, so nothing is allocated on the GPU while the size stays the same.
+    pub fn render_preview(
+        &self,
+        gl: &glow::Context,
+        max_dim: u32,
+        uniform_data: UniformData,
+    ) -> Result<([u32; 2], Vec<u8>), String> {
+        self.render_preview_pixels(
+            gl,
+            max_dim,
+            uniform_data,
+            glow::RGBA8,
+            glow::UNSIGNED_BYTE,
+            1,
+        )
+    }
+
+    /// Like [`Self::render_preview`], but returns the iteration counts of
+    /// [`Self::render_iterations`]. Needs desktop GL like it.
+    pub fn render_preview_iterations(
+        &self,
+        gl: &glow::Context,
+        max_dim: u32,
+        uniform_data: UniformData,
+    ) -> Result<([u32; 2], Vec<u16>), String> {
+        let uniform_data = UniformData {
+            output_layer: OutputLayer::Iterations,
+            ..uniform_data
+        };
+        let (size, bytes) = self.render_preview_pixels(
+            gl,
+            max_dim,
+            uniform_data,
+            glow::RGBA16,
+            glow::UNSIGNED_SHORT,
+            2,
+        )?;
+        // the count is in the red channel
+        let counts = bytes
+            .chunks_exact(2)
+            .step_by(4)
+            .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+            .collect();
+        Ok((size, counts))
+    }
+
+    /// Framebuffer of the cached preview target with `internal_format` and a side of at least
+    /// `side`, made when there's none yet.
+    unsafe fn preview_target(
+        &self,
+        gl: &glow::Context,
+        side: u32,
+        internal_format: u32,
+        data_type: u32,
+    ) -> Result<glow::Framebuffer, String> {
+        let mut targets = self.preview_targets.borrow_mut();
+        match targets.get(&internal_format) {
+            Some(&(_, framebuffer, cached_side)) if cached_side >= side => return Ok(framebuffer),
+            Some(&(texture, framebuffer, _)) => {
+                gl.delete_framebuffer(framebuffer);
+                gl.delete_texture(texture);
+                targets.remove(&internal_format);
+            }
+            None => {}
+        }
+
+        let texture = gl.create_texture()?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            internal_format as i32,
+            side as i32,
+            side as i32,
+            0,
+            glow::RGBA,
+            data_type,
+            None,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::NEAREST as i32,
+        );
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        let framebuffer = match gl.create_framebuffer() {
+            Ok(framebuffer) => framebuffer,
+            Err(error) => {
+                gl.delete_texture(texture);
+                return Err(error);
+            }
+        };
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(texture),
+            0,
+        );
+        let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        if status != glow::FRAMEBUFFER_COMPLETE {
+            gl.delete_framebuffer(framebuffer);
+            gl.delete_texture(texture);
+            return Err(format!(
+                "The graphics driver can't render a preview in this format \
+                 (framebuffer status {status:#x})"
+            ));
+        }
+        targets.insert(internal_format, (texture, framebuffer, side));
+        Ok(framebuffer)
+    }
+
+    /// Renders a preview into the cached target with the given format and returns its size
+    /// and raw contents, see [`Self::render_preview`].
+    fn render_preview_pixels(
+        &self,
+        gl: &glow::Context,
+        max_dim: u32,
+        uniform_data: UniformData,
+        internal_format: u32,
+        data_type: u32,
+        bytes_per_channel: usize,
+    ) -> Result<([u32; 2], Vec<u8>), String> {
         use glow::HasContext as _;
 
+        self.check_thread();
+        let max_dim = max_dim.min(self.max_texture_size);
+        let resolution = uniform_data.resolution;
+        let scale = (max_dim as f32 / resolution.max_elem()).min(1.);
+        let (width, height) = (
+            (resolution.x * scale).round() as u32,
+            (resolution.y * scale).round() as u32,
+        );
+        if width == 0 || height == 0 {
+            return Err(format!("Can't render a preview of {width}×{height} px"));
+        }
+
         unsafe {
+            let framebuffer = self.preview_target(gl, max_dim, internal_format, data_type)?;
+            let viewport = [0, 0, width as i32, height as i32];
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            // only the corner the preview fills is cleared and drawn
+            gl.enable(glow::SCISSOR_TEST);
+            gl.scissor(0, 0, width as i32, height as i32);
+            self.clear(gl, uniform_data);
+            gl.disable(glow::BLEND);
+            self.paint_into(gl, Some(framebuffer), viewport, uniform_data);
+            gl.disable(glow::SCISSOR_TEST);
+
+            let row = width as usize * 4 * bytes_per_channel;
+            let mut pixels = vec![0; row * height as usize];
+            gl.read_pixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                glow::RGBA,
+                data_type,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            let pixels = pixels.chunks_exact(row).rev().flatten().copied().collect();
+            Ok(([width, height], pixels))
+        }
+    }
+
+    /// Renders into a texture with the given format and returns its raw contents. Fails
+    /// without rendering when the texture can't be created or can't be rendered into.
+    #[allow(clippy::too_many_arguments)]
+    fn render_pixels(
+        &self,
+        gl: &glow::Context,
+        width: u32,
+        height: u32,
+        uniform_data: UniformData,
+        progress: &mut dyn FnMut(f32),
+        internal_format: u32,
+        data_type: u32,
+        bytes_per_channel: usize,
+    ) -> Result<Vec<u8>, String> {
+        use glow::HasContext as _;
+
+        self.check_thread();
+        if width == 0 || height == 0 || width.max(height) > self.max_texture_size {
+            return Err(format!(
+                "Can't render {width}×{height} px, the texture limit is {} px",
+                self.max_texture_size
+            ));
+        }
+        unsafe {
+            // earlier errors would be mistaken for ones of the texture
+            while gl.get_error() != glow::NO_ERROR {}
+
             // Create a texture to render into
-            let texture = gl
-                .create_texture()
-                .expect("Failed to create texture for framebuffer");
+            let texture = gl.create_texture()?;
             gl.bind_texture(glow::TEXTURE_2D, Some(texture));
             gl.tex_image_2d(
                 glow::TEXTURE_2D,
                 0,
-                glow::RGBA as i32,
+                internal_format as i32,
                 width as i32,
                 height as i32,
                 0,
                 glow::RGBA,
-                glow::UNSIGNED_BYTE,
+                data_type,
                 None,
             );
+            // the texture is only read back pixel by pixel, never sampled, so filtering doesn't
+            // change the result, see `Pixels::downsample` for how exports are scaled
             gl.tex_parameter_i32(
                 glow::TEXTURE_2D,
                 glow::TEXTURE_MIN_FILTER,
-                glow::LINEAR as i32,
+                glow::NEAREST as i32,
             );
             gl.tex_parameter_i32(
                 glow::TEXTURE_2D,
                 glow::TEXTURE_MAG_FILTER,
-                glow::LINEAR as i32,
+                glow::NEAREST as i32,
             );
 
+            if gl.get_error() == glow::OUT_OF_MEMORY {
+                gl.delete_texture(texture);
+                return Err(format!(
+                    "The graphics driver is out of memory for a {width}×{height} px image"
+                ));
+            }
+
             // Create a framebuffer and attach the texture
-            let framebuffer = gl
-                .create_framebuffer()
-                .expect("Failed to create framebuffer");
+            let framebuffer = match gl.create_framebuffer() {
+                Ok(framebuffer) => framebuffer,
+                Err(error) => {
+                    gl.delete_texture(texture);
+                    return Err(error);
+                }
+            };
             gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
             gl.framebuffer_texture_2d(
                 glow::FRAMEBUFFER,
@@ -189,10 +1147,17 @@ impl Renderer {
                 0,
             );
 
-            assert!(
-                gl.check_framebuffer_status(glow::FRAMEBUFFER) == glow::FRAMEBUFFER_COMPLETE,
-                "Framebuffer is not complete"
-            );
+            // e.g. when the driver can't render into this format or size
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                gl.delete_framebuffer(framebuffer);
+                gl.delete_texture(texture);
+                return Err(format!(
+                    "The graphics driver can't render a {width}×{height} px image in this \
+                     format (framebuffer status {status:#x})"
+                ));
+            }
 
             // Set the viewport to the size of the texture
             gl.viewport(0, 0, width as i32, height as i32);
@@ -202,18 +1167,31 @@ impl Renderer {
                 window_offset: (0., 0.).into(),
                 ..uniform_data
             };
-            println!("{uniform_data:#?}");
-            self.paint(gl, uniform_data);
+            self.clear(gl, uniform_data);
+            // egui leaves blending on, which would fill transparent pixels with the background,
+            // it sets up its own state again before painting
+            gl.disable(glow::BLEND);
+            gl.enable(glow::SCISSOR_TEST);
+            for y in (0..height).step_by(RENDER_STRIP_HEIGHT as usize) {
+                let rows = RENDER_STRIP_HEIGHT.min(height - y);
+                gl.scissor(0, y as i32, width as i32, rows as i32);
+                self.paint(gl, uniform_data);
+                // wait for the strip, so the reported progress is real
+                gl.finish();
+                progress((y + rows) as f32 / height as f32);
+            }
+            gl.disable(glow::SCISSOR_TEST);
 
             // Read the pixels back from the framebuffer
-            let mut pixels: Vec<u8> = vec![0; (width * height * 4) as usize];
+            let mut pixels: Vec<u8> =
+                vec![0; width as usize * height as usize * 4 * bytes_per_channel];
             gl.read_pixels(
                 0,
                 0,
                 width as i32,
                 height as i32,
                 glow::RGBA,
-                glow::UNSIGNED_BYTE,
+                data_type,
                 glow::PixelPackData::Slice(&mut pixels),
             );
 
@@ -222,7 +1200,298 @@ impl Renderer {
             gl.delete_framebuffer(framebuffer);
             gl.delete_texture(texture);
 
-            pixels
+            // OpenGL reads the bottom row first, images start with the top one
+            let row = width as usize * 4 * bytes_per_channel;
+            Ok(pixels
+                .chunks_exact(row.max(1))
+                .rev()
+                .flatten()
+                .copied()
+                .collect())
+        }
+    }
+}
+
+/// Renders one `width` by `height` RGBA image (8 bits per channel, top row first) of
+/// `fractal_function`, which defines `vec2 iteration(vec2 previous_z, vec2 original_z)`
+/// like the `*_FUNC` constants. Set `uniform_data.convergent` for [`NEWTON_FUNC`].
+///
+/// `gl` needs a current OpenGL 3.3 (or WebGL 2) context, no window or default framebuffer
+/// is used, e.g. a surfaceless EGL context works. The GL objects are created and deleted
+/// here, and the default framebuffer is bound afterwards. Use a [`Renderer`] directly to
+/// render several frames without recompiling the shader.
+pub fn render_frame(
+    gl: &glow::Context,
+    fractal_function: &str,
+    width: u32,
+    height: u32,
+    uniform_data: UniformData,
+) -> Result<Vec<u8>, String> {
+    let mut renderer = Renderer::new(gl)?;
+    let result = renderer
+        .set_fractal_function(gl, fractal_function)
+        .map_err(|error| error.log)
+        .and_then(|()| {
+            let uniform_data = UniformData {
+                resolution: egui::vec2(width as f32, height as f32),
+                window_offset: egui::Vec2::ZERO,
+                ..uniform_data
+            };
+            renderer.render_to_buffer(gl, width, height, uniform_data, &mut |_| {})
+        });
+    renderer.destroy(gl);
+    result
+}
+
+/// Longest fractal function that is compiled, the compiler could take very long on longer ones.
+pub const MAX_FUNCTION_LENGTH: usize = 64 * 1024;
+
+/// Checks a fractal function from outside of the app, like a file or saved settings, before
+/// it's compiled. Rejects functions longer than [`MAX_FUNCTION_LENGTH`] and removes control
+/// characters, which GLSL doesn't allow and which (like a NUL) can cut the source short.
+pub fn sanitize_fractal_function(fractal_function: &str) -> Result<String, String> {
+    if fractal_function.len() > MAX_FUNCTION_LENGTH {
+        return Err(format!(
+            "The function is {} KB long, the limit is {} KB",
+            fractal_function.len() / 1024,
+            MAX_FUNCTION_LENGTH / 1024
+        ));
+    }
+    Ok(fractal_function
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        .collect())
+}
+
+/// The `iteration` function of [`morph_function`], calling the two renamed ones.
+const MORPH_ITERATION: &str = r#"
+vec2 iteration(vec2 previous_z, vec2 original_z) {
+    vec2 from_z = iteration_from(previous_z, original_z);
+    return mix(from_z, iteration_to(previous_z, original_z), morph);
+}
+"#;
+
+/// Combines two fractal functions into one mixing their results by the `morph` uniform,
+/// which goes from `from` at 0 to `to` at 1.
+pub fn morph_function(from: &str, to: &str) -> String {
+    let from = from.replace("iteration(", "iteration_from(");
+    let to = to.replace("iteration(", "iteration_to(");
+    format!("{from}\n{to}\n{MORPH_ITERATION}")
+}
+
+/// Finds the line number of the first error in a compiler log. Drivers write it as
+/// `0:LINE(COLUMN)` (Mesa), `0(LINE)` (Nvidia) or `ERROR: 0:LINE:` (ANGLE).
+fn log_line(log: &str) -> Option<u32> {
+    log.lines().find_map(|line| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("ERROR:").unwrap_or(line).trim_start();
+        // the number of the source string comes first
+        let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+        if rest.len() == line.len() {
+            return None;
+        }
+        let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('('))?;
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        rest[..end].parse().ok()
+    })
+}
+
+/// Fragment shader of [`probe_double_float`], after `double_float.glsl`. Writes white if the
+/// pairs of `operands.x + operands.y` and `operands.x * operands.x` come out as `expected`.
+const DOUBLE_FLOAT_PROBE: &str = r#"
+void main() {
+    vec2 sum = two_sum(operands.x, operands.y);
+    vec2 product = two_prod(operands.x, operands.x);
+    fragColor = vec4(sum == expected.xy && product == expected.zw ? 1. : 0.);
+}
+"#;
+
+/// Checks whether the driver computes the pairs of `double_float.glsl` exactly, which needs
+/// correctly rounded floats and no reordering of the operations, by drawing a single pixel.
+/// The operands are uniforms, so the compiler can't fold them away.
+unsafe fn probe_double_float(gl: &glow::Context, vertex_array: glow::VertexArray) -> bool {
+    let source = format!(
+        "precision highp float;\nuniform vec2 operands;\nuniform vec4 expected;\n\
+         out vec4 fragColor;\n{}\n{}",
+        include_str!("double_float.glsl"),
+        DOUBLE_FLOAT_PROBE
+    );
+    let Ok(program) = link_program(gl, &source, 0) else {
+        return false;
+    };
+    // the product and the sum both have errors below the precision of a float
+    let (a, b) = (1. + 2f32.powi(-12) + 2f32.powi(-22), 3. * 2f32.powi(-30));
+    let split = |exact: f64| (exact as f32, (exact - exact as f32 as f64) as f32);
+    let (sum, sum_error) = split(a as f64 + b as f64);
+    let (product, product_error) = split(a as f64 * a as f64);
+
+    let mut pixel = [0u8; 4];
+    let (Ok(texture), Ok(framebuffer)) = (gl.create_texture(), gl.create_framebuffer()) else {
+        gl.delete_program(program);
+        return false;
+    };
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    gl.tex_image_2d(
+        glow::TEXTURE_2D,
+        0,
+        glow::RGBA8 as i32,
+        1,
+        1,
+        0,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        None,
+    );
+    gl.bind_texture(glow::TEXTURE_2D, None);
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+    gl.framebuffer_texture_2d(
+        glow::FRAMEBUFFER,
+        glow::COLOR_ATTACHMENT0,
+        glow::TEXTURE_2D,
+        Some(texture),
+        0,
+    );
+    if gl.check_framebuffer_status(glow::FRAMEBUFFER) == glow::FRAMEBUFFER_COMPLETE {
+        gl.viewport(0, 0, 1, 1);
+        gl.disable(glow::BLEND);
+        gl.disable(glow::SCISSOR_TEST);
+        gl.use_program(Some(program));
+        gl.uniform_2_f32(gl.get_uniform_location(program, "operands").as_ref(), a, b);
+        gl.uniform_1_f32(gl.get_uniform_location(program, "df_one").as_ref(), 1.);
+        gl.uniform_4_f32(
+            gl.get_uniform_location(program, "expected").as_ref(),
+            sum,
+            sum_error,
+            product,
+            product_error,
+        );
+        gl.bind_vertex_array(Some(vertex_array));
+        gl.draw_arrays(glow::TRIANGLES, 0, 6);
+        gl.read_pixels(
+            0,
+            0,
+            1,
+            1,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelPackData::Slice(&mut pixel),
+        );
+    }
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+    gl.delete_framebuffer(framebuffer);
+    gl.delete_texture(texture);
+    gl.delete_program(program);
+    pixel[0] == 255
+}
+
+/// Compiles and links the fragment shader with `fractal_function` appended to it.
+unsafe fn create_program(
+    gl: &glow::Context,
+    fractal_function: &str,
+) -> Result<glow::Program, ShaderError> {
+    let fractal_function = sanitize_fractal_function(fractal_function)?;
+    let fragment_shader_source = assemble_fragment_shader(&fractal_function);
+    // lines of the fragment shader before the fractal function, counting the version line
+    let function_start = fragment_shader_source.len() - fractal_function.len();
+    let lines_before_function = fragment_shader_source[..function_start].lines().count() as u32 + 1;
+    link_program(gl, &fragment_shader_source, lines_before_function)
+}
+
+/// The fragment shader with `fractal_function` appended, without the version line.
+fn assemble_fragment_shader(fractal_function: &str) -> String {
+    format!(
+        "{}\n{}\n{}",
+        include_str!("frag.glsl"),
+        include_str!("double_float.glsl"),
+        fractal_function
+    )
+}
+
+/// The complete fragment shader compiled for `fractal_function`, version line included, as
+/// the driver gets it. Fails like [`sanitize_fractal_function`].
+pub fn fragment_shader_source(fractal_function: &str) -> Result<String, String> {
+    let fractal_function = sanitize_fractal_function(fractal_function)?;
+    Ok(format!(
+        "{SHADER_VERSION}\n{}",
+        assemble_fragment_shader(&fractal_function)
+    ))
+}
+
+/// Compiles `fragment_shader_source` with the vertex shader drawing the whole viewport.
+/// Lines of errors in the fragment shader are reported relative to `lines_before_function`.
+unsafe fn link_program(
+    gl: &glow::Context,
+    fragment_shader_source: &str,
+    lines_before_function: u32,
+) -> Result<glow::Program, ShaderError> {
+    let program = gl.create_program()?;
+    let shader_sources = [
+        (glow::VERTEX_SHADER, VERTEX_SHADER),
+        (glow::FRAGMENT_SHADER, fragment_shader_source),
+    ];
+
+    let mut shaders = Vec::with_capacity(shader_sources.len());
+    let mut result = Ok(());
+    for (shader_type, shader_source) in shader_sources {
+        let shader = match gl.create_shader(shader_type) {
+            Ok(shader) => shader,
+            Err(error) => {
+                result = Err(error.into());
+                break;
+            }
+        };
+        gl.shader_source(shader, &format!("{}\n{}", SHADER_VERSION, shader_source));
+        gl.compile_shader(shader);
+        if !gl.get_shader_compile_status(shader) {
+            let log = gl.get_shader_info_log(shader);
+            let line = (shader_type == glow::FRAGMENT_SHADER)
+                .then(|| log_line(&log))
+                .flatten()
+                .and_then(|line| line.checked_sub(lines_before_function))
+                .filter(|&line| line > 0);
+            result = Err(ShaderError { log, line });
+        }
+        gl.attach_shader(program, shader);
+        shaders.push(shader);
+    }
+
+    if result.is_ok() {
+        gl.link_program(program);
+        if !gl.get_program_link_status(program) {
+            result = Err(gl.get_program_info_log(program).into());
+        }
+    }
+
+    for shader in shaders {
+        gl.detach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
+
+    match result {
+        Ok(()) => Ok(program),
+        Err(error) => {
+            gl.delete_program(program);
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_vertex_array_holds_no_state_of_the_program() {
+        // the single vertex array survives program swaps only while no program reads
+        // attributes from it
+        for line in VERTEX_SHADER.lines().map(str::trim) {
+            assert!(
+                !line.starts_with("in ") && !line.starts_with("layout"),
+                "the vertex shader reads an attribute: {line}"
+            );
         }
+        assert!(VERTEX_SHADER.contains("verts[gl_VertexID]"));
     }
 }
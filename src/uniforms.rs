@@ -0,0 +1,475 @@
+use egui::{epaint::Hsva, vec2, Vec2};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+use crate::{fixed::Fixed, palette::GradientStops, view::ViewTransform};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UniformData {
+    pub center: Vec2,
+    /// Point of the plane `center` is measured from, in fixed point. Zero unless deep zoom
+    /// is active, then the view is moved into it by [`Self::rebase`] whenever it gets far
+    /// from it, and `center` only holds the small offset from it.
+    pub origin: [Fixed; 2],
+    pub zoom: f32,
+    /// Counterclockwise rotation of the view in degrees.
+    pub rotation: f32,
+    /// Set from the widget size every frame.
+    #[serde(skip)]
+    pub resolution: Vec2,
+    #[serde(skip)]
+    pub window_offset: Vec2,
+    pub cycles: i32,
+    #[serde(with = "hsva_serde")]
+    pub start_color: Hsva,
+    #[serde(with = "hsva_serde")]
+    pub end_color: Hsva,
+    /// How the gradient blends from `start_color` to `end_color`.
+    pub interpolation: Interpolation,
+    /// Fixed gradient used instead of the one from `start_color` to `end_color`.
+    pub colormap: Option<Colormap>,
+    /// Gradient imported from a file, used instead of the colors like `colormap`.
+    pub gradient_stops: Option<GradientStops>,
+    /// Color pixels by the point their orbit converges to instead of by escape time.
+    /// Follows from the fractal type.
+    #[serde(skip)]
+    pub convergent: bool,
+    /// Stop iterating once the orbit returns to an earlier iterate, which speeds up the
+    /// interior of the set. Only correct when the next iterate depends on the current one
+    /// alone, so it follows from the fractal type and the user's toggle.
+    #[serde(skip)]
+    pub period_checking: bool,
+    /// Iterate the Mandelbrot set as an offset from the orbit of `origin`, which is computed
+    /// in fixed point by [`crate::renderer::Renderer::update_reference_orbit`]. Only
+    /// works with the Mandelbrot function.
+    #[serde(skip)]
+    pub perturbation: bool,
+    /// Iterate the Mandelbrot set from `origin` in pairs of floats, which carry about twice
+    /// the precision of one. Cheaper than `perturbation` but doesn't reach as deep, and only
+    /// works where [`crate::renderer::Renderer::double_float_supported`].
+    #[serde(skip)]
+    pub double_float: bool,
+    pub newton_degree: i32,
+    pub phoenix_p: f32,
+    /// Parameters for custom functions, which can mix the two constants by `blend`.
+    pub constants: [Vec2; 2],
+    pub blend: f32,
+    /// Point the Mandelbrot and Phoenix orbits start at instead of zero. Custom functions
+    /// can read it as `z_seed`.
+    pub z_seed: Vec2,
+    /// Progress of the transition drawn by a program from `morph_function`.
+    #[serde(skip)]
+    pub morph: f32,
+    pub coloring_mode: ColoringMode,
+    /// Number of discrete color steps, 0 for a continuous gradient.
+    pub color_bands: i32,
+    /// Shift of the gradient, in `0..1`.
+    pub color_offset: f32,
+    /// Repeat the gradient past its end, so `color_offset` wraps around instead of stopping
+    /// at the end color.
+    pub cyclic_gradient: bool,
+    pub smoothing: Smoothing,
+    /// Escape iterations at the start and end of the gradient, `None` to span `0..cycles`.
+    /// Keeps the colors of a view when only `cycles` changes, convergent fractals are
+    /// darkened over the same range.
+    pub color_range: Option<[i32; 2]>,
+    /// Add noise of about one 8-bit step to the colors, which hides banding in
+    /// subtle gradients.
+    pub dither: bool,
+    /// How the edges of the set are smoothed in the view and in exports.
+    pub antialiasing: Antialiasing,
+    /// Added to the red, green and blue channels of the final color, 0 keeps them.
+    pub brightness: [f32; 3],
+    /// Scales the red, green and blue channels of the final color about their middle,
+    /// 1 keeps them.
+    pub contrast: [f32; 3],
+    /// Let graded colors leave `0..=1`, set by the renderer for floating point exports.
+    #[serde(skip)]
+    pub unclamped: bool,
+    /// Color the view is cleared with before the fractal is painted over it.
+    #[serde(with = "hsva_serde")]
+    pub background_color: Hsva,
+    /// Write zero alpha for points in the set, so exports can be composited over something
+    /// else. Only set for exports, the view would blend them with the UI.
+    #[serde(skip)]
+    pub transparent_interior: bool,
+    /// Set by the renderer for exports of data instead of colors.
+    #[serde(skip)]
+    pub output_layer: OutputLayer,
+}
+
+impl UniformData {
+    /// The mapping between the view and the plane.
+    pub fn transform(&self) -> ViewTransform {
+        ViewTransform {
+            center: self.center,
+            zoom: self.zoom,
+            rotation: self.rotation,
+            resolution: self.resolution,
+        }
+    }
+
+    /// Moves the view to `transform`, keeping the resolution.
+    fn set_transform(&mut self, transform: ViewTransform) {
+        self.center = transform.center;
+        self.zoom = transform.zoom;
+        self.rotation = transform.rotation;
+    }
+
+    /// See [`ViewTransform::zoom_at`].
+    pub fn zoom_at(&mut self, factor: f32, anchor: Vec2) {
+        let mut transform = self.transform();
+        transform.zoom_at(factor, anchor);
+        self.set_transform(transform);
+    }
+
+    /// See [`ViewTransform::center_on`].
+    pub fn center_on(&mut self, point: Vec2) {
+        let mut transform = self.transform();
+        transform.center_on(point - self.origin_f32());
+        self.set_transform(transform);
+    }
+
+    /// Moves `origin` to `point`, keeping all of its digits, and the middle of the view to
+    /// `offset` from there.
+    pub fn center_near(&mut self, point: [Fixed; 2], offset: Vec2) {
+        self.origin = point;
+        let mut transform = self.transform();
+        transform.center_on(offset);
+        self.set_transform(transform);
+    }
+
+    /// See [`ViewTransform::fit`].
+    pub fn fit(&mut self, region: egui::Rect) {
+        let mut transform = self.transform();
+        transform.fit(region.translate(-self.origin_f32()));
+        self.set_transform(transform);
+    }
+
+    /// See [`ViewTransform::plane_point`].
+    pub fn plane_point(&self, fraction: Vec2) -> Vec2 {
+        self.transform().plane_point(fraction) + self.origin_f32()
+    }
+
+    /// See [`ViewTransform::view_fraction`].
+    pub fn view_fraction(&self, point: Vec2) -> Vec2 {
+        self.transform().view_fraction(point - self.origin_f32())
+    }
+
+    /// The point in the middle of the view, with `origin` added in fixed point.
+    pub fn center_point(&self) -> [Fixed; 2] {
+        let [x, y] = self.center_offset();
+        [
+            self.origin[0] + Fixed::from_f64(x),
+            self.origin[1] + Fixed::from_f64(y),
+        ]
+    }
+
+    /// The middle of the view relative to `origin`, like [`ViewTransform::plane_point`] but
+    /// in double precision, which the rotation doesn't affect there.
+    fn center_offset(&self) -> [f64; 2] {
+        let aspect = if self.resolution.x > 0. {
+            self.resolution.y as f64 / self.resolution.x as f64
+        } else {
+            1.
+        };
+        let zoom = self.zoom as f64;
+        [
+            self.center.x as f64 / zoom,
+            self.center.y as f64 * aspect / zoom,
+        ]
+    }
+
+    fn origin_f32(&self) -> Vec2 {
+        vec2(
+            self.origin[0].to_f64() as f32,
+            self.origin[1].to_f64() as f32,
+        )
+    }
+
+    /// Moves the middle of the view into `origin` and sets `center` to zero, so `center`
+    /// stays small and precise however deep the view is.
+    pub fn rebase(&mut self) {
+        self.origin = self.center_point();
+        self.center = Vec2::ZERO;
+    }
+
+    /// Undoes [`Self::rebase`], moving `origin` back into `center`.
+    pub fn flatten_origin(&mut self) {
+        if self.origin != [Fixed::ZERO; 2] {
+            let point = self.plane_point(vec2(0.5, 0.5));
+            self.origin = [Fixed::ZERO; 2];
+            self.center_on(point);
+        }
+    }
+
+    /// Escape iterations mapped to the start and end of the gradient.
+    pub fn gradient_range(&self) -> [i32; 2] {
+        self.color_range.unwrap_or([0, self.cycles])
+    }
+
+    /// Zoom relative to the initial view.
+    pub fn magnification(&self) -> f32 {
+        self.zoom / DEFAULT_ZOOM
+    }
+
+    /// Returns the view showing just `region` of this one, where `region` is given in fractions
+    /// of the view with the origin at its top left corner. The resolution is set to the size
+    /// of the region in pixels, scale it uniformly to render the crop at a higher resolution.
+    pub fn cropped(&self, region: egui::Rect) -> Self {
+        let size = region.size();
+        let offset = self
+            .transform()
+            .view_offset(region.center().to_vec2() - vec2(0.5, 0.5));
+        Self {
+            center: (self.center + offset) / size,
+            zoom: self.zoom / size.x,
+            resolution: (self.resolution * size).round(),
+            ..*self
+        }
+    }
+}
+
+/// [`Hsva`] doesn't implement serde traits, so it's stored as `[h, s, v, a]`.
+pub mod hsva_serde {
+    use egui::epaint::Hsva;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Hsva, serializer: S) -> Result<S::Ok, S::Error> {
+        [color.h, color.s, color.v, color.a].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hsva, D::Error> {
+        let [h, s, v, a] = <[f32; 4]>::deserialize(deserializer)?;
+        Ok(Hsva { h, s, v, a })
+    }
+}
+
+impl Default for UniformData {
+    fn default() -> Self {
+        let (start_color, end_color) = default_colors(true);
+        Self {
+            center: Vec2::ZERO,
+            origin: [Fixed::ZERO; 2],
+            zoom: DEFAULT_ZOOM,
+            rotation: 0.,
+            resolution: Vec2::ZERO,
+            window_offset: Vec2::ZERO,
+            cycles: 100,
+            start_color,
+            end_color,
+            interpolation: Interpolation::Hsv,
+            colormap: None,
+            gradient_stops: None,
+            convergent: false,
+            period_checking: false,
+            perturbation: false,
+            double_float: false,
+            newton_degree: 3,
+            phoenix_p: -0.5,
+            constants: [vec2(-0.8, 0.156), vec2(0.285, 0.01)],
+            blend: 0.,
+            z_seed: Vec2::ZERO,
+            morph: 0.,
+            coloring_mode: ColoringMode::EscapeTime,
+            color_bands: 0,
+            color_offset: 0.,
+            cyclic_gradient: false,
+            smoothing: Smoothing::Linear,
+            color_range: None,
+            dither: false,
+            antialiasing: Antialiasing::None,
+            brightness: [0.; 3],
+            contrast: [1.; 3],
+            unclamped: false,
+            background_color: Hsva::new(0., 0., 0., 1.),
+            transparent_interior: false,
+            output_layer: OutputLayer::Color,
+        }
+    }
+}
+
+/// What value of an escaped orbit is mapped onto the color gradient.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColoringMode {
+    #[default]
+    EscapeTime = 0,
+    OrbitAverage = 1,
+    FinalAngle = 2,
+}
+
+impl ColoringMode {
+    pub const ALL: [ColoringMode; 3] = [
+        ColoringMode::EscapeTime,
+        ColoringMode::OrbitAverage,
+        ColoringMode::FinalAngle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColoringMode::EscapeTime => "Escape time",
+            ColoringMode::OrbitAverage => "Orbit average",
+            ColoringMode::FinalAngle => "Final angle",
+        }
+    }
+}
+
+/// Color space the gradient is blended in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// Blends hue, saturation and value, so the hue sweeps around the color wheel.
+    #[default]
+    Hsv = 0,
+    /// Blends linear RGB, going straight from one color to the other.
+    Rgb = 1,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 2] = [Interpolation::Hsv, Interpolation::Rgb];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Interpolation::Hsv => "HSV",
+            Interpolation::Rgb => "RGB",
+        }
+    }
+}
+
+/// Gradients with many stops, designed to stay legible with color vision deficiencies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Colormap {
+    /// Dark blue over green to yellow, evenly increasing in lightness.
+    Viridis,
+    /// Like viridis, but optimized to look the same with red-green color blindness.
+    Cividis,
+    /// Blue over white to red, for telling the two ends apart.
+    BlueRed,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 3] = [Colormap::Viridis, Colormap::Cividis, Colormap::BlueRed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Viridis => "Viridis",
+            Colormap::Cividis => "Cividis",
+            Colormap::BlueRed => "Blue–red",
+        }
+    }
+}
+
+/// What the shader writes for each pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputLayer {
+    #[default]
+    Color = 0,
+    /// The raw iteration count, see [`crate::renderer::Renderer::render_iterations`].
+    Iterations = 1,
+    /// White for points in the set, transparent elsewhere,
+    /// see [`crate::renderer::Renderer::render_set_mask`].
+    SetMask = 2,
+}
+
+/// How the escape iteration is normalized before the gradient lookup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Smoothing {
+    #[default]
+    Linear = 0,
+    Continuous = 1,
+    SquareRoot = 2,
+    Logarithmic = 3,
+}
+
+impl Smoothing {
+    pub const ALL: [Smoothing; 4] = [
+        Smoothing::Linear,
+        Smoothing::Continuous,
+        Smoothing::SquareRoot,
+        Smoothing::Logarithmic,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Smoothing::Linear => "Linear",
+            Smoothing::Continuous => "Continuous",
+            Smoothing::SquareRoot => "Square root",
+            Smoothing::Logarithmic => "Logarithmic",
+        }
+    }
+}
+
+/// How pixels on the edge of the set are smoothed, on top of supersampled exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Antialiasing {
+    #[default]
+    None = 0,
+    /// Blends pixels whose escape count changes quickly between neighbors with them, which
+    /// costs almost nothing but blurs the edge a little.
+    Derivative = 1,
+    /// Averages four samples per pixel, four times as slow.
+    Supersample = 2,
+}
+
+impl Antialiasing {
+    pub const ALL: [Antialiasing; 3] = [
+        Antialiasing::None,
+        Antialiasing::Derivative,
+        Antialiasing::Supersample,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Antialiasing::None => "None",
+            Antialiasing::Derivative => "Derivative",
+            Antialiasing::Supersample => "Supersample",
+        }
+    }
+}
+
+/// How the Mandelbrot set is iterated once the zoom passes [`DEEP_ZOOM_START`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeepZoom {
+    /// Stay in single precision, which turns into blocks a little deeper.
+    #[default]
+    Off,
+    /// See [`UniformData::double_float`].
+    DoubleFloat,
+    /// See [`UniformData::perturbation`].
+    Perturbation,
+}
+
+impl DeepZoom {
+    pub const ALL: [DeepZoom; 3] = [DeepZoom::Off, DeepZoom::DoubleFloat, DeepZoom::Perturbation];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DeepZoom::Off => "Off",
+            DeepZoom::DoubleFloat => "Float pairs",
+            DeepZoom::Perturbation => "Perturbation",
+        }
+    }
+}
+
+/// Zoom of the initial view, which shows the whole Mandelbrot set.
+pub const DEFAULT_ZOOM: f32 = 0.2;
+
+/// Bounds of `UniformData::zoom`, outside of them the view can't be recovered. Single
+/// precision breaks down long before the upper one, perturbation goes on to where the
+/// offsets of neighboring pixels get too small for single precision.
+pub const ZOOM_RANGE: RangeInclusive<f32> = 1e-3..=1e30;
+
+/// Zoom from which deep zoom iterates relative to a reference orbit, before single
+/// precision shows.
+pub const DEEP_ZOOM_START: f32 = 1e4;
+
+/// Default gradient `(start, end)`, flipped in light mode so the exterior doesn't blend into the UI.
+pub fn default_colors(dark_mode: bool) -> (Hsva, Hsva) {
+    let white = Hsva::new(1., 0., 1., 1.);
+    let black = Hsva::new(0., 0., 0., 1.);
+    if dark_mode {
+        (white, black)
+    } else {
+        (black, white)
+    }
+}
@@ -0,0 +1,219 @@
+use egui::{vec2, Rect, Vec2};
+
+use crate::uniforms::ZOOM_RANGE;
+
+/// Maps between positions in a view and points of the plane the way `frag.glsl` does,
+/// without needing a GL context.
+///
+/// Positions in the view are fractions of its size with the origin at the top left corner.
+/// `center` is the point in the middle of the view multiplied by `zoom`, with its imaginary
+/// part divided by the aspect ratio, which is the form the shader takes it in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewTransform {
+    pub center: Vec2,
+    pub zoom: f32,
+    /// Counterclockwise rotation of the view in degrees.
+    pub rotation: f32,
+    /// Size of the view in pixels, only its aspect ratio matters.
+    pub resolution: Vec2,
+}
+
+impl ViewTransform {
+    /// Height of the view relative to its width.
+    fn aspect(&self) -> f32 {
+        if self.resolution.x > 0. {
+            self.resolution.y / self.resolution.x
+        } else {
+            1.
+        }
+    }
+
+    /// Converts an offset from the middle of the view, as a fraction of its size, to the
+    /// units of `center`, taking the rotation into account.
+    pub fn view_offset(&self, offset: Vec2) -> Vec2 {
+        let aspect = self.aspect();
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let offset = vec2(offset.x, offset.y * aspect);
+        let rotated = vec2(
+            cos * offset.x - sin * offset.y,
+            sin * offset.x + cos * offset.y,
+        );
+        vec2(rotated.x, rotated.y / aspect)
+    }
+
+    /// The point at `fraction` of the view in the units of `center`, which is what
+    /// [`Self::zoom_at`] takes.
+    pub fn anchor(&self, fraction: Vec2) -> Vec2 {
+        self.view_offset(fraction - vec2(0.5, 0.5)) + self.center
+    }
+
+    /// Converts a position in the view to the point of the plane `frag.glsl` iterates there.
+    pub fn plane_point(&self, fraction: Vec2) -> Vec2 {
+        let pos = self.anchor(fraction) / self.zoom;
+        vec2(pos.x, pos.y * self.aspect())
+    }
+
+    /// Inverse of [`Self::plane_point`], returns where `point` of the plane is shown in the
+    /// view, which is outside of `0..=1` for points that aren't visible.
+    pub fn view_fraction(&self, point: Vec2) -> Vec2 {
+        let aspect = self.aspect();
+        let offset = vec2(point.x, point.y / aspect) * self.zoom - self.center;
+        // undo the rotation of `view_offset`
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let offset = vec2(offset.x, offset.y * aspect);
+        let unrotated = vec2(
+            cos * offset.x + sin * offset.y,
+            -sin * offset.x + cos * offset.y,
+        );
+        vec2(unrotated.x, unrotated.y / aspect) + vec2(0.5, 0.5)
+    }
+
+    /// Moves the view so `point` of the plane is in its middle, at the current zoom.
+    pub fn center_on(&mut self, point: Vec2) {
+        self.center = vec2(point.x, point.y / self.aspect()) * self.zoom;
+    }
+
+    /// Moves and zooms the view so all of `region` of the plane is visible, as large as fits.
+    pub fn fit(&mut self, region: Rect) {
+        let size = region.size();
+        let zoom = (1. / size.x).min(self.aspect() / size.y);
+        self.zoom = zoom.clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end());
+        self.center_on(region.center().to_vec2());
+    }
+
+    /// Moves the view along with a drag of `delta`, as a fraction of its size, so the point
+    /// under the pointer follows it.
+    pub fn pan(&mut self, delta: Vec2) {
+        self.center -= self.view_offset(delta);
+    }
+
+    /// Multiplies the zoom by `factor` while keeping `anchor` (see [`Self::anchor`]) at the
+    /// same place in the view.
+    pub fn zoom_at(&mut self, factor: f32, anchor: Vec2) {
+        let old_zoom = self.zoom;
+        let zoom = (old_zoom * factor).clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end());
+        if !zoom.is_finite() {
+            return;
+        }
+        // use the clamped factor, so the point doesn't drift at the limits
+        let factor = zoom / old_zoom;
+        self.zoom = zoom;
+        self.center += anchor * (factor - 1.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view() -> ViewTransform {
+        ViewTransform {
+            center: vec2(0.3, -0.2),
+            zoom: 1.7,
+            rotation: 33.,
+            resolution: vec2(300., 120.),
+        }
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn view_fraction_inverts_plane_point() {
+        let view = view();
+        for fraction in [vec2(0.1, 0.2), vec2(0.9, 0.5), vec2(1.5, -0.3)] {
+            assert_close(view.view_fraction(view.plane_point(fraction)), fraction);
+        }
+    }
+
+    #[test]
+    fn zoom_keeps_the_anchor_in_place() {
+        let mut view = view();
+        let fraction = vec2(0.8, 0.25);
+        let point = view.plane_point(fraction);
+        view.zoom_at(3., view.anchor(fraction));
+        assert_close(view.plane_point(fraction), point);
+        view.zoom_at(0.1, view.anchor(fraction));
+        assert_close(view.plane_point(fraction), point);
+    }
+
+    #[test]
+    fn zoom_stops_at_the_limits() {
+        let mut view = view();
+        let fraction = vec2(0.2, 0.7);
+        let point = view.plane_point(fraction);
+        view.zoom_at(1e35, view.anchor(fraction));
+        assert_eq!(view.zoom, *ZOOM_RANGE.end());
+        assert!((view.plane_point(fraction) - point).length() < 1e-3);
+    }
+
+    #[test]
+    fn panning_follows_the_pointer() {
+        let mut view = view();
+        let point = view.plane_point(vec2(0.4, 0.4));
+        view.pan(vec2(0.2, -0.1));
+        assert_close(view.plane_point(vec2(0.6, 0.3)), point);
+    }
+
+    #[test]
+    fn pans_compose() {
+        let (mut once, mut twice) = (view(), view());
+        once.pan(vec2(0.3, 0.1));
+        twice.pan(vec2(0.1, 0.3));
+        twice.pan(vec2(0.2, -0.2));
+        assert_close(once.center, twice.center);
+    }
+
+    #[test]
+    fn pixels_are_square() {
+        let view = ViewTransform {
+            rotation: 0.,
+            ..view()
+        };
+        let across = view.plane_point(vec2(1., 0.5)) - view.plane_point(vec2(0., 0.5));
+        let down = view.plane_point(vec2(0.5, 1.)) - view.plane_point(vec2(0.5, 0.));
+        // the view is 300 px wide and 120 px high
+        assert!((across.length() / down.length() - 2.5).abs() < 1e-4);
+        // the imaginary part grows downwards, like screen coordinates
+        assert!(down.y > 0.);
+    }
+
+    #[test]
+    fn rotation_turns_the_view() {
+        let view = ViewTransform {
+            rotation: 90.,
+            resolution: vec2(100., 100.),
+            ..view()
+        };
+        // a quarter turn, so going right on screen follows the imaginary axis
+        let right = view.plane_point(vec2(1., 0.5)) - view.plane_point(vec2(0.5, 0.5));
+        assert!(right.y > 0. && right.x.abs() < 1e-4);
+    }
+
+    #[test]
+    fn fit_shows_the_whole_region() {
+        let mut view = ViewTransform {
+            rotation: 0.,
+            ..view()
+        };
+        let region = Rect::from_x_y_ranges(-2.5..=1., -1.25..=1.25);
+        view.fit(region);
+        let (min, max) = (
+            view.plane_point(vec2(0., 0.)),
+            view.plane_point(vec2(1., 1.)),
+        );
+        assert!(min.x <= region.min.x + 1e-4 && min.y <= region.min.y + 1e-4);
+        assert!(max.x >= region.max.x - 1e-4 && max.y >= region.max.y - 1e-4);
+        // the view is wider than the region, so it fills the height
+        assert!((max.y - min.y - region.height()).abs() < 1e-4);
+        assert_close(view.plane_point(vec2(0.5, 0.5)), region.center().to_vec2());
+    }
+
+    #[test]
+    fn center_on_puts_the_point_in_the_middle() {
+        let mut view = view();
+        view.center_on(vec2(-0.75, 0.1));
+        assert_close(view.plane_point(vec2(0.5, 0.5)), vec2(-0.75, 0.1));
+    }
+}
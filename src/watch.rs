@@ -0,0 +1,44 @@
+use std::{path::PathBuf, time::SystemTime};
+
+/// Watches a file by polling its modification time.
+pub struct FileWatcher {
+    pub path: PathBuf,
+    modified: Option<SystemTime>,
+    failed: bool,
+}
+
+impl FileWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+            failed: false,
+        }
+    }
+
+    /// Returns the contents of the file if it changed since the last call,
+    /// errors are reported only once until the file becomes readable again.
+    pub fn poll(&mut self) -> Option<Result<String, String>> {
+        let result = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .and_then(|modified| {
+                if self.modified == Some(modified) && !self.failed {
+                    return Ok(None);
+                }
+                self.modified = Some(modified);
+                std::fs::read_to_string(&self.path).map(Some)
+            });
+
+        match result {
+            Ok(contents) => {
+                self.failed = false;
+                contents.map(Ok)
+            }
+            Err(_) if self.failed => None,
+            Err(e) => {
+                self.failed = true;
+                Some(Err(format!("Cannot read {}: {e}", self.path.display())))
+            }
+        }
+    }
+}